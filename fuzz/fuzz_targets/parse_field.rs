@@ -0,0 +1,29 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use just_in_time_game::Field;
+
+// Most bytes are mapped onto the characters of the field format, so that the fuzzer mostly
+// produces valid-ish boards. The remaining bytes are passed through as they are.
+const ALPHABET: &[u8] = b" -123456789\n";
+
+fuzz_target!(|data: &[u8]| {
+    let input: String = data
+        .iter()
+        .map(|&b| match ALPHABET.get(usize::from(b) % 32) {
+            Some(&c) => c as char,
+            None => b as char,
+        })
+        .collect();
+
+    if let Ok(field) = input.parse::<Field>() {
+        let reparsed: Field = field
+            .to_string()
+            .parse()
+            .expect("the output of Display can't be parsed");
+        assert_eq!(field.field, reparsed.field);
+        if !field.field.is_empty() {
+            assert_eq!(field.width, reparsed.width);
+        }
+    }
+});
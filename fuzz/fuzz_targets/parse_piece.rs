@@ -0,0 +1,30 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use just_in_time_game::Piece;
+
+// Most bytes are mapped onto the characters of the piece format, so that the fuzzer mostly
+// produces valid-ish pieces. The remaining bytes are passed through as they are.
+const ALPHABET: &[u8] = b"X \n";
+
+fuzz_target!(|data: &[u8]| {
+    let input: String = data
+        .iter()
+        .map(|&b| match ALPHABET.get(usize::from(b) % 8) {
+            Some(&c) => c as char,
+            None => b as char,
+        })
+        .collect();
+
+    if let Ok(piece) = input.parse::<Piece>() {
+        let reparsed: Piece = piece
+            .to_string()
+            .parse()
+            .expect("the output of Display can't be parsed");
+        assert!(piece == reparsed);
+
+        for variant in piece.all_variants() {
+            assert_eq!(variant.occupied_count(), piece.occupied_count());
+        }
+    }
+});
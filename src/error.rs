@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::piece::MAX_PIECES;
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// the input didn't contain a single cell
+    Empty,
+    UnexpectedCharacter(char),
+    /// only ASCII is supported, apart from custom piece characters
+    NonAscii(char),
+    /// a malformed `FIELD <width>x<height>` header
+    InvalidHeader,
+    /// the content doesn't fit into the declared dimensions
+    DimensionMismatch,
+    /// a score of an overlay that isn't a number or doesn't belong to a free cell
+    InvalidScore(String),
+    /// a solution code that doesn't match its template
+    InvalidCode,
+    /// a `.gz` file that isn't valid gzip data
+    InvalidGzip,
+    /// a puzzle file that isn't valid TOML or doesn't follow the puzzle schema
+    InvalidToml(String),
+    /// more pieces than fit into the single byte of a piece id
+    TooManyPieces,
+    Io(io::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Empty => write!(f, "at least one cell is required"),
+            ParseError::UnexpectedCharacter(c) => write!(f, "unexpected character {:?}", c),
+            ParseError::NonAscii(c) => write!(f, "unsupported non-ASCII character {:?}", c),
+            ParseError::InvalidHeader => write!(f, "invalid header"),
+            ParseError::DimensionMismatch => write!(f, "content exceeds the declared dimensions"),
+            ParseError::InvalidScore(ref score) => write!(f, "invalid score {:?}", score),
+            ParseError::InvalidCode => write!(f, "invalid solution code"),
+            ParseError::InvalidGzip => write!(f, "invalid gzip data"),
+            ParseError::InvalidToml(ref message) => write!(f, "invalid TOML: {}", message),
+            ParseError::TooManyPieces => write!(f, "at most {} pieces are supported", MAX_PIECES),
+            ParseError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ParseError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
@@ -0,0 +1,1631 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::ParseError;
+use crate::piece::{piece_char, Connectivity, Piece};
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum FieldState {
+    Blocked,
+    /// id of the element
+    Occupied(u8),
+    ///< score
+    Free(i16),
+}
+
+impl FieldState {
+    /// Single character representation, scores that don't fit into one digit are shown as `?`.
+    fn to_char(&self) -> char {
+        match *self {
+            FieldState::Blocked => ' ',
+            FieldState::Free(0) => '-',
+            FieldState::Free(n @ 1..=9) => (b'0' + n as u8) as char,
+            FieldState::Free(_) => '?',
+            FieldState::Occupied(n) => piece_char(n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Field {
+    pub width: usize,
+    pub height: usize,
+    pub field: Vec<FieldState>,
+    /// named groups of cells, tagged with lowercase letters in the field file
+    pub zones: BTreeMap<char, Vec<(usize, usize)>>,
+}
+
+impl Field {
+    /// Strict variant of `from_str` where `#` marks blocked cells and spaces are rejected, so
+    /// trimmed trailing whitespace can't change the board. All rows must have the same length.
+    pub fn from_str_hashblocked(s: &str) -> Result<Field, ParseError> {
+        let mut lines: Vec<&str> = s.split_terminator('\n').collect();
+        let header = match lines.first() {
+            Some(line) if line.starts_with("FIELD ") => Some(lines.remove(0)),
+            _ => None,
+        };
+
+        if lines.iter().any(|line| line.contains(' ')) {
+            return Err(ParseError::UnexpectedCharacter(' '));
+        }
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        if lines.iter().any(|line| line.chars().count() != width) {
+            return Err(ParseError::DimensionMismatch);
+        }
+
+        let rows: Vec<String> = header
+            .into_iter()
+            .map(str::to_string)
+            .chain(lines.iter().map(|line| line.replace('#', " ")))
+            .collect();
+        rows.join("\n").parse()
+    }
+
+    pub fn place_iter<'a>(&'a self, piece: &'a Piece) -> PlaceIterator<'a> {
+        PlaceIterator {
+            field: self,
+            piece,
+            x: 0,
+            y: 0,
+        }
+    }
+
+    /// Like `place_iter`, but the free cells of `piece` may hang off the board. Only the free
+    /// rows and columns at the border of a piece can do so, so this places the trimmed piece.
+    pub fn place_iter_allow_overhang<'a>(
+        &'a self,
+        piece: &Piece,
+    ) -> impl Iterator<Item = Field> + 'a {
+        let piece = piece.trimmed();
+        let xs = (self.width + 1).saturating_sub(piece.width);
+        let ys = (self.height + 1).saturating_sub(piece.height);
+        (0..ys)
+            .flat_map(move |y| (0..xs).map(move |x| (x, y)))
+            .filter_map(move |(x, y)| self.with_piece_placed(&piece, x, y))
+    }
+
+    /// The placement of any variant of `piece` that covers the most score, together with the
+    /// covered score. Ties are resolved in favor of the first placement found.
+    pub fn best_single_placement(&self, piece: &Piece) -> Option<(Field, i32)> {
+        let score = self.count();
+        let mut best: Option<(Field, i32)> = None;
+
+        for variant in piece.all_variants().iter() {
+            for placement in self.place_iter(variant) {
+                let gained = score - placement.count();
+                let better = match best {
+                    Some((_, best_gained)) => gained > best_gained,
+                    None => true,
+                };
+                if better {
+                    best = Some((placement, gained));
+                }
+            }
+        }
+
+        best
+    }
+
+    /// The placements of `piece` that cover `cell`.
+    pub fn placements_covering<'a>(
+        &'a self,
+        piece: &'a Piece,
+        cell: (usize, usize),
+    ) -> impl Iterator<Item = Field> + 'a {
+        let inside = cell.0 < self.width && cell.1 < self.height;
+        let i = if inside {
+            cell.0 + cell.1 * self.width
+        } else {
+            0
+        };
+        let free = inside && matches!(self.field[i], FieldState::Free(_));
+        self.place_iter(piece)
+            .filter(move |placement| free && placement.field[i] == FieldState::Occupied(piece.id))
+    }
+
+    /// The placements of `piece` that only cover free cells with a score of at least
+    /// `min_score`.
+    pub fn placements_on_min_score<'a>(
+        &'a self,
+        piece: &'a Piece,
+        min_score: i16,
+    ) -> impl Iterator<Item = Field> + 'a {
+        self.place_iter(piece).filter(move |placement| {
+            placement
+                .field
+                .iter()
+                .zip(self.field.iter())
+                .all(|(after, before)| match before {
+                    FieldState::Free(score) => after == before || *score >= min_score,
+                    _ => true,
+                })
+        })
+    }
+
+    /// The placements of `piece` that don't cut off a single free cell next to the piece.
+    pub fn placements_no_orphan<'a>(
+        &'a self,
+        piece: &'a Piece,
+    ) -> impl Iterator<Item = Field> + 'a {
+        self.place_iter(piece)
+            .filter(move |placement| !placement.has_orphan_next_to_changes(self))
+    }
+
+    /// Whether a cell that changed compared to `before` touches a free cell without free
+    /// neighbours.
+    fn has_orphan_next_to_changes(&self, before: &Field) -> bool {
+        let neighbours = |i: usize| {
+            Connectivity::Four
+                .neighbors(i % self.width, i / self.width, self.width, self.height)
+                .into_iter()
+                .map(|(x, y)| x + y * self.width)
+        };
+        let is_free = |i: usize| matches!(self.field[i], FieldState::Free(_));
+
+        (0..self.field.len())
+            .filter(|&i| self.field[i] != before.field[i])
+            .flat_map(neighbours)
+            .any(|n| is_free(n) && !neighbours(n).any(is_free))
+    }
+
+    /// Whether the bounding box of `piece` at `x`/`y` lies within the field.
+    fn contains(&self, piece: &Piece, x: usize, y: usize) -> bool {
+        let inside = |start: usize, size: usize, limit: usize| {
+            start.checked_add(size).is_some_and(|end| end <= limit)
+        };
+        inside(x, piece.width, self.width) && inside(y, piece.height, self.height)
+    }
+
+    /// Whether `piece` fits with its top left corner at `x`/`y`.
+    pub fn can_place(&self, piece: &Piece, x: usize, y: usize) -> bool {
+        if !self.contains(piece, x, y) {
+            return false;
+        }
+        if piece.must_touch_border && !self.touches_border(piece, x, y) {
+            return false;
+        }
+
+        for piece_x in 0..piece.width {
+            for piece_y in 0..piece.height {
+                if piece.field[piece_x + piece_y * piece.width].is_occupied()
+                    && !matches!(
+                        self.field[x + piece_x + (y + piece_y) * self.width],
+                        FieldState::Free(_)
+                    )
+                {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Whether an occupied cell of `piece` at `x`/`y` lies on the outer row or column.
+    fn touches_border(&self, piece: &Piece, x: usize, y: usize) -> bool {
+        (0..piece.field.len())
+            .filter(|&i| piece.field[i].is_occupied())
+            .any(|i| {
+                let (x, y) = (x + i % piece.width, y + i / piece.width);
+                x == 0 || y == 0 || x + 1 == self.width || y + 1 == self.height
+            })
+    }
+
+    /// Number of placements of all variants of `piece`, without building the placed fields.
+    pub fn count_placements(&self, piece: &Piece) -> usize {
+        piece
+            .all_variants()
+            .iter()
+            .map(|variant| {
+                let xs = (self.width + 1).saturating_sub(variant.width);
+                let ys = (self.height + 1).saturating_sub(variant.height);
+                (0..ys)
+                    .flat_map(|y| (0..xs).map(move |x| (x, y)))
+                    .filter(|&(x, y)| self.can_place(variant, x, y))
+                    .count()
+            })
+            .sum()
+    }
+
+    /// The field with `piece` placed with its top left corner at `x`/`y`, or `None` if it
+    /// doesn't fit there.
+    pub fn with_piece_placed(&self, piece: &Piece, x: usize, y: usize) -> Option<Field> {
+        if !self.can_place(piece, x, y) {
+            return None;
+        }
+
+        let mut ret = self.clone();
+
+        for piece_x in 0..piece.width {
+            for piece_y in 0..piece.height {
+                if piece.field[piece_x + piece_y * piece.width].is_occupied() {
+                    ret.field[x + piece_x + (y + piece_y) * ret.width] =
+                        FieldState::Occupied(piece.id);
+                }
+            }
+        }
+
+        Some(ret)
+    }
+
+    /// Places `piece` at `x`/`y` in place, leaves the field unchanged and returns false if it
+    /// doesn't fit there.
+    pub fn try_place(&mut self, piece: &Piece, x: usize, y: usize) -> bool {
+        match self.with_piece_placed(piece, x, y) {
+            Some(placed) => {
+                *self = placed;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Places all `moves` in order, or none of them. Returns the index of the first move that
+    /// doesn't fit, the field is left unchanged in that case.
+    pub fn apply_all(&mut self, moves: &[(Piece, usize, usize)]) -> Result<(), usize> {
+        let mut result = self.clone();
+        for (i, (piece, x, y)) in moves.iter().enumerate() {
+            if !result.try_place(piece, *x, *y) {
+                return Err(i);
+            }
+        }
+
+        *self = result;
+        Ok(())
+    }
+
+    /// Frees the cells of piece `id` again, restoring their scores from `start`. Returns false if
+    /// the piece isn't placed.
+    pub fn remove_placement(&mut self, id: u8, start: &Field) -> bool {
+        let mut removed = false;
+        for (cell, original) in self.field.iter_mut().zip(start.field.iter()) {
+            if *cell == FieldState::Occupied(id) {
+                *cell = original.clone();
+                removed = true;
+            }
+        }
+        removed
+    }
+
+    /// Number of free cells `piece` covers when placed at `x`/`y`, `None` if it doesn't fit.
+    pub fn placement_coverage(&self, piece: &Piece, x: usize, y: usize) -> Option<usize> {
+        if self.can_place(piece, x, y) {
+            Some(piece.occupied_count())
+        } else {
+            None
+        }
+    }
+
+    /// Upper bound of the score `pieces` can cover: the sum of the highest scores of as many
+    /// free cells as the pieces occupy.
+    pub fn max_possible_score(&self, pieces: &[Piece]) -> i32 {
+        let mut scores: Vec<i32> = self
+            .field
+            .iter()
+            .filter_map(|state| match *state {
+                FieldState::Free(score) => Some(i32::from(score)),
+                _ => None,
+            })
+            .collect();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+
+        let cells = pieces.iter().map(Piece::occupied_count).sum();
+        scores.iter().take(cells).sum()
+    }
+
+    /// Free cells that no placement of any of `pieces` covers.
+    pub fn uncoverable_free_cells(&self, pieces: &[Piece]) -> Vec<(usize, usize)> {
+        let mut covered = vec![false; self.field.len()];
+        for piece in pieces.iter() {
+            for variant in piece.all_variants().iter() {
+                for placement in self.place_iter(variant) {
+                    for (i, state) in placement.field.iter().enumerate() {
+                        if *state != self.field[i] {
+                            covered[i] = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.field
+            .iter()
+            .enumerate()
+            .filter(|&(i, state)| matches!(*state, FieldState::Free(_)) && !covered[i])
+            .map(|(i, _)| (i % self.width, i / self.width))
+            .collect()
+    }
+
+    /// Checks that the cells match the dimensions of the field.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.field.len() != self.width * self.height {
+            return Err(format!(
+                "{} cells don't match the dimensions {}x{}",
+                self.field.len(),
+                self.width,
+                self.height
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Like `validate`, but additionally checks that the field can be the result of placing
+    /// pieces on `start`: only free cells may have become occupied, everything else is unchanged.
+    pub fn validate_against(&self, start: &Field) -> Result<(), String> {
+        self.validate()?;
+
+        if self.width != start.width || self.height != start.height {
+            return Err("the dimensions differ from the start field".to_string());
+        }
+
+        for (i, (state, original)) in self.field.iter().zip(start.field.iter()).enumerate() {
+            let coherent = match (original, state) {
+                (FieldState::Free(_), FieldState::Occupied(_)) => true,
+                _ => original == state,
+            };
+            if !coherent {
+                return Err(format!(
+                    "cell {}/{} changed from {:?} to {:?}",
+                    i % self.width,
+                    i / self.width,
+                    original,
+                    state
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Id of the piece occupying the cell, `None` for free, blocked or out of range cells.
+    pub fn piece_at(&self, x: usize, y: usize) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        match self.field[x + y * self.width] {
+            FieldState::Occupied(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// `(min_x, min_y, max_x, max_y)` of the occupied cells, `None` if there are none.
+    pub fn occupied_bounds(&self) -> Option<(usize, usize, usize, usize)> {
+        self.field
+            .iter()
+            .enumerate()
+            .filter(|&(_, state)| matches!(*state, FieldState::Occupied(_)))
+            .map(|(i, _)| (i % self.width, i / self.width))
+            .fold(None, |bounds, (x, y)| match bounds {
+                None => Some((x, y, x, y)),
+                Some((x0, y0, x1, y1)) => Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y))),
+            })
+    }
+
+    /// The shape of piece `id` as placed on this board, `None` if it isn't placed.
+    pub fn extract_piece(&self, id: u8) -> Option<Piece> {
+        let coords: Vec<(usize, usize)> = self
+            .field
+            .iter()
+            .enumerate()
+            .filter(|&(_, state)| *state == FieldState::Occupied(id))
+            .map(|(i, _)| (i % self.width, i / self.width))
+            .collect();
+
+        if coords.is_empty() {
+            return None;
+        }
+
+        let mut piece = Piece::from_coords(&coords);
+        piece.id = id;
+        Some(piece)
+    }
+
+    /// Sum of the scores in `original` of the cells each piece covers. Signed, as overlays can
+    /// assign negative scores.
+    pub fn score_by_piece(&self, original: &Field) -> HashMap<u8, i32> {
+        let mut scores = HashMap::new();
+        for (state, before) in self.field.iter().zip(original.field.iter()) {
+            if let (FieldState::Occupied(id), FieldState::Free(score)) = (state, before) {
+                *scores.entry(*id).or_insert(0) += i32::from(*score);
+            }
+        }
+        scores
+    }
+
+    /// Fraction between 0 and 1 of the free cells of `original` that are occupied now, 0 if
+    /// `original` has no free cells.
+    pub fn fill_percent(&self, original: &Field) -> f64 {
+        let mut free = 0;
+        let mut filled = 0;
+        for (state, before) in self.field.iter().zip(original.field.iter()) {
+            if let FieldState::Free(_) = before {
+                free += 1;
+                if let FieldState::Occupied(_) = state {
+                    filled += 1;
+                }
+            }
+        }
+
+        if free == 0 {
+            0.0
+        } else {
+            f64::from(filled) / f64::from(free)
+        }
+    }
+
+    /// The cells of the zone `tag`, regardless of whether they are still free.
+    pub fn zone_cells(&self, tag: char) -> &[(usize, usize)] {
+        self.zones.get(&tag).map_or(&[], Vec::as_slice)
+    }
+
+    /// Number of cell edges where a piece touches another piece, a blocked cell or the border.
+    pub fn edge_contact(&self) -> usize {
+        let occupied = |state: &FieldState| matches!(*state, FieldState::Occupied(_));
+        let touching = |a: &FieldState, b: &FieldState| match (a, b) {
+            (FieldState::Occupied(a), FieldState::Occupied(b)) => a != b,
+            (FieldState::Occupied(_), FieldState::Blocked)
+            | (FieldState::Blocked, FieldState::Occupied(_)) => true,
+            _ => false,
+        };
+
+        let mut contact = 0;
+        for (i, state) in self.field.iter().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            if occupied(state) {
+                contact += [x == 0, x + 1 == self.width, y == 0, y + 1 == self.height]
+                    .iter()
+                    .filter(|&&border| border)
+                    .count();
+            }
+            if x + 1 < self.width && touching(state, &self.field[i + 1]) {
+                contact += 1;
+            }
+            if y + 1 < self.height && touching(state, &self.field[i + self.width]) {
+                contact += 1;
+            }
+        }
+
+        contact
+    }
+
+    /// The regions of free cells under `connectivity`, ordered by their first cell.
+    pub fn free_components(&self, connectivity: Connectivity) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = vec![false; self.field.len()];
+        let mut components = vec![];
+
+        for start in 0..self.field.len() {
+            if seen[start] || !matches!(self.field[start], FieldState::Free(_)) {
+                continue;
+            }
+
+            seen[start] = true;
+            let mut component = vec![];
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                let (x, y) = (i % self.width, i / self.width);
+                component.push((x, y));
+
+                for (x, y) in connectivity.neighbors(x, y, self.width, self.height) {
+                    let n = x + y * self.width;
+                    if !seen[n] && matches!(self.field[n], FieldState::Free(_)) {
+                        seen[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+
+            component.sort_by_key(|&(x, y)| (y, x));
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// One field per 4-connected free region, with the free cells of all other regions blocked.
+    /// Pieces can't bridge diagonal gaps, so the regions are independent.
+    pub fn partition_into_independent(&self) -> Vec<Field> {
+        self.free_components(Connectivity::Four)
+            .iter()
+            .map(|component| {
+                let mut part = self.clone();
+                for state in part.field.iter_mut() {
+                    if let FieldState::Free(_) = *state {
+                        *state = FieldState::Blocked;
+                    }
+                }
+                for &(x, y) in component {
+                    part.field[x + y * self.width] = self.field[x + y * self.width].clone();
+                }
+                part
+            })
+            .collect()
+    }
+
+    /// `Display` output with every row right padded with spaces to at least `width` characters.
+    pub fn to_padded_string(&self, width: usize) -> String {
+        self.to_string()
+            .split('\n')
+            .map(|row| format!("{:<width$}", row, width = width))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Renders every cell as a block of three characters with `|` and `-` separating cells that
+    /// belong to different pieces, or to a piece and the free or blocked cells around it.
+    pub fn to_boxed_string(&self) -> String {
+        let owner = |x: usize, y: usize| match self.field[x + y * self.width] {
+            FieldState::Blocked => 0,
+            FieldState::Free(_) => 1,
+            FieldState::Occupied(id) => 2 + u16::from(id),
+        };
+        let horizontal = |x: usize, y: usize| {
+            x < self.width && (y == 0 || y == self.height || owner(x, y - 1) != owner(x, y))
+        };
+        let vertical = |x: usize, y: usize| {
+            y < self.height && (x == 0 || x == self.width || owner(x - 1, y) != owner(x, y))
+        };
+
+        let mut lines = vec![];
+        for y in 0..=self.height {
+            let mut line = String::new();
+            for x in 0..=self.width {
+                let corner = horizontal(x, y)
+                    || vertical(x, y)
+                    || (x > 0 && horizontal(x - 1, y))
+                    || (y > 0 && vertical(x, y - 1));
+                line.push(if corner { '+' } else { ' ' });
+                if x < self.width {
+                    line.push_str(if horizontal(x, y) { "---" } else { "   " });
+                }
+            }
+            lines.push(line);
+
+            if y < self.height {
+                let mut line = String::new();
+                for x in 0..=self.width {
+                    line.push(if vertical(x, y) { '|' } else { ' ' });
+                    if x < self.width {
+                        line.push(' ');
+                        line.push(self.field[x + y * self.width].to_char());
+                        line.push(' ');
+                    }
+                }
+                lines.push(line);
+            }
+        }
+
+        lines.join("\n")
+    }
+
+    /// Blocks the cells of the rectangle, the parts outside of the field are ignored.
+    pub fn block_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.fill_rect(x, y, w, h, FieldState::Blocked);
+    }
+
+    /// Frees the cells of the rectangle with `score`, the parts outside of the field are ignored.
+    pub fn free_rect(&mut self, x: usize, y: usize, w: usize, h: usize, score: i16) {
+        self.fill_rect(x, y, w, h, FieldState::Free(score));
+    }
+
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, state: FieldState) {
+        for field_y in y..y.saturating_add(h).min(self.height) {
+            for field_x in x..x.saturating_add(w).min(self.width) {
+                self.field[field_x + field_y * self.width] = state.clone();
+            }
+        }
+    }
+
+    /// Sets the scores of free cells from `overlay`, which allows scores that can't be written
+    /// as a single character, like penalties. Every row contains whitespace separated scores for
+    /// the cells of the row, `.` keeps the score of a cell. Like everywhere else these scores
+    /// are shown as `?`, so a field with them doesn't round-trip through `Display`.
+    pub fn apply_score_overlay(&mut self, overlay: &str) -> Result<(), ParseError> {
+        let rows: Vec<&str> = overlay.split_terminator('\n').collect();
+        if rows.len() > self.height {
+            return Err(ParseError::DimensionMismatch);
+        }
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, token) in row.split_whitespace().enumerate() {
+                if x >= self.width {
+                    return Err(ParseError::DimensionMismatch);
+                }
+                if token == "." {
+                    continue;
+                }
+
+                let score = token
+                    .parse()
+                    .map_err(|_| ParseError::InvalidScore(token.to_string()))?;
+                match self.field[x + y * self.width] {
+                    FieldState::Free(ref mut s) => *s = score,
+                    _ => return Err(ParseError::InvalidScore(token.to_string())),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn count(&self) -> i32 {
+        self.field.iter().fold(0i32, |acc, field| {
+            acc + match *field {
+                FieldState::Free(score) => i32::from(score),
+                _ => 0,
+            }
+        })
+    }
+}
+
+/// Renders `a`, marking every cell whose state differs from `b`: occupied cells are shown in
+/// lower case, or as `+` for ids whose character has no lower case, all others as `*`.
+pub fn highlight_diff(a: &Field, b: &Field) -> Result<String, &'static str> {
+    if a.width != b.width || a.height != b.height {
+        return Err("fields have different dimensions");
+    }
+
+    let mut result = String::new();
+    for (i, (state, other)) in a.field.iter().zip(b.field.iter()).enumerate() {
+        if i % a.width == 0 && i != 0 {
+            result.push('\n');
+        }
+        let c = match *state {
+            FieldState::Occupied(n) if state != other => {
+                let upper = piece_char(n);
+                let mut lower = upper.to_lowercase();
+                match (lower.next(), lower.next()) {
+                    (Some(c), None) if c != upper => c,
+                    _ => '+',
+                }
+            }
+            _ if state != other => '*',
+            _ => state.to_char(),
+        };
+        result.push(c);
+    }
+
+    Ok(result)
+}
+
+/// SVG document with a square of `cell` pixels per cell, colored by the piece covering it.
+/// Free cells show their score unless it is 0.
+pub fn render_svg(field: &Field, cell: u32) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        field.width as u32 * cell,
+        field.height as u32 * cell
+    );
+
+    for (i, state) in field.field.iter().enumerate() {
+        let x = (i % field.width) as u32 * cell;
+        let y = (i / field.width) as u32 * cell;
+        let color = match *state {
+            FieldState::Blocked => "#444444".to_string(),
+            FieldState::Free(_) => "#ffffff".to_string(),
+            FieldState::Occupied(id) => piece_color(id),
+        };
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#000000\"/>\n",
+            x, y, cell, cell, color
+        );
+
+        if let FieldState::Free(score) = *state {
+            if score != 0 {
+                svg += &format!(
+                    "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                    x + cell / 2,
+                    y + cell / 2,
+                    score
+                );
+            }
+        }
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+/// A field whose free cells can be covered by up to `max_overlap` pieces. `field` shows the
+/// piece on top of every cell.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct LayeredField {
+    pub field: Field,
+    /// number of pieces covering each cell
+    pub depth: Vec<u8>,
+    pub max_overlap: u8,
+}
+
+impl LayeredField {
+    pub fn new(field: Field, max_overlap: u8) -> LayeredField {
+        let depth = vec![0; field.field.len()];
+        LayeredField {
+            field,
+            depth,
+            max_overlap,
+        }
+    }
+
+    /// Whether `piece` fits at `x`/`y` without stacking any cell deeper than `max_overlap`. Only
+    /// the free cells of the original field can be covered, pieces that were placed on it
+    /// before count as full.
+    pub fn can_place(&self, piece: &Piece, x: usize, y: usize) -> bool {
+        if !self.field.contains(piece, x, y) {
+            return false;
+        }
+        if piece.must_touch_border && !self.field.touches_border(piece, x, y) {
+            return false;
+        }
+
+        (0..piece.field.len())
+            .filter(|&i| piece.field[i].is_occupied())
+            .all(|i| {
+                let cell = x + i % piece.width + (y + i / piece.width) * self.field.width;
+                // a cell without a layer still shows the original field
+                let free =
+                    self.depth[cell] > 0 || matches!(self.field.field[cell], FieldState::Free(_));
+                free && self.depth[cell] < self.max_overlap
+            })
+    }
+
+    /// Places `piece` on top at `x`/`y`, returns false if it doesn't fit there.
+    pub fn try_place(&mut self, piece: &Piece, x: usize, y: usize) -> bool {
+        if !self.can_place(piece, x, y) {
+            return false;
+        }
+
+        for i in (0..piece.field.len()).filter(|&i| piece.field[i].is_occupied()) {
+            let cell = x + i % piece.width + (y + i / piece.width) * self.field.width;
+            self.field.field[cell] = FieldState::Occupied(piece.id);
+            self.depth[cell] += 1;
+        }
+        true
+    }
+
+    /// All placements of `piece`, like `Field::place_iter`.
+    pub fn place_iter<'a>(&'a self, piece: &'a Piece) -> impl Iterator<Item = LayeredField> + 'a {
+        let xs = (self.field.width + 1).saturating_sub(piece.width);
+        let ys = (self.field.height + 1).saturating_sub(piece.height);
+        (0..ys)
+            .flat_map(move |y| (0..xs).map(move |x| (x, y)))
+            .filter_map(move |(x, y)| {
+                let mut placed = self.clone();
+                if placed.try_place(piece, x, y) {
+                    Some(placed)
+                } else {
+                    None
+                }
+            })
+    }
+}
+
+impl fmt::Display for LayeredField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.field.fmt(f)
+    }
+}
+
+fn piece_color(id: u8) -> String {
+    format!("hsl({}, 70%, 60%)", piece_hue(id))
+}
+
+/// Hue of the color of a piece, consecutive ids are spread by the golden angle.
+pub(crate) fn piece_hue(id: u8) -> u32 {
+    u32::from(id) * 137 % 360
+}
+
+/// Grid of a contact sheet: the canonical orientations of the pieces, the number of columns and
+/// rows and the size of a slot in cells. Every slot is one cell wider than the widest piece and
+/// has an extra row on top for the label.
+pub(crate) struct CatalogLayout {
+    pub shapes: Vec<Piece>,
+    pub cols: usize,
+    pub rows: usize,
+    pub slot_width: u32,
+    pub slot_height: u32,
+}
+
+impl CatalogLayout {
+    pub fn new(pieces: &[Piece], cols: usize) -> CatalogLayout {
+        let shapes: Vec<Piece> = pieces
+            .iter()
+            .map(|piece| {
+                let mut shape = piece.canonical();
+                shape.id = piece.id;
+                shape
+            })
+            .collect();
+        let cols = cols.max(1);
+        CatalogLayout {
+            rows: shapes.len().div_ceil(cols),
+            cols,
+            slot_width: shapes.iter().map(|s| s.width).max().unwrap_or(0) as u32 + 1,
+            slot_height: shapes.iter().map(|s| s.height).max().unwrap_or(0) as u32 + 2,
+            shapes,
+        }
+    }
+}
+
+/// SVG contact sheet of the canonical orientations of `pieces`, laid out in a grid with `cols`
+/// columns and labeled with `Piece <id>`, see `CatalogLayout`.
+pub fn render_catalog_svg(pieces: &[Piece], cell: u32, cols: usize) -> String {
+    let layout = CatalogLayout::new(pieces, cols);
+    let slot_width = layout.slot_width * cell;
+    let slot_height = layout.slot_height * cell;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        layout.cols as u32 * slot_width,
+        layout.rows as u32 * slot_height
+    );
+
+    for (n, shape) in layout.shapes.iter().enumerate() {
+        let left = (n % layout.cols) as u32 * slot_width + cell / 2;
+        let top = (n / layout.cols) as u32 * slot_height;
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" dominant-baseline=\"central\">Piece {}</text>\n",
+            left,
+            top + cell / 2,
+            piece_char(shape.id)
+        );
+
+        for (i, state) in shape.field.iter().enumerate() {
+            if state.is_occupied() {
+                svg += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#000000\"/>\n",
+                    left + (i % shape.width) as u32 * cell,
+                    top + (i / shape.width) as u32 * cell + cell,
+                    cell,
+                    cell,
+                    piece_color(shape.id)
+                );
+            }
+        }
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+pub struct PlaceIterator<'a> {
+    field: &'a Field,
+    piece: &'a Piece,
+    x: usize,
+    y: usize,
+}
+
+impl<'a> PlaceIterator<'a> {
+    /// Yields the position of the piece together with each placement.
+    pub fn with_positions(mut self) -> impl Iterator<Item = (Field, (usize, usize))> + 'a {
+        std::iter::from_fn(move || self.next_positioned())
+    }
+
+    fn next_positioned(&mut self) -> Option<(Field, (usize, usize))> {
+        loop {
+            let field_offset_x = self.x;
+            let field_offset_y = self.y;
+
+            if self.piece.width > self.field.width
+                || field_offset_y + self.piece.height > self.field.height
+            {
+                return None; // and we're done
+            }
+
+            self.x += 1;
+
+            if self.x + self.piece.width > self.field.width {
+                self.x = 0;
+                self.y += 1;
+            }
+
+            if let Some(ret) =
+                self.field
+                    .with_piece_placed(self.piece, field_offset_x, field_offset_y)
+            {
+                return Some((ret, (field_offset_x, field_offset_y)));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for PlaceIterator<'a> {
+    type Item = Field;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_positioned().map(|(field, _)| field)
+    }
+}
+
+/// The most cells a `FIELD` header may declare.
+pub(crate) const MAX_CELLS: usize = 1 << 20;
+
+impl FromStr for Field {
+    type Err = ParseError;
+
+    /// An optional `FIELD <width>x<height>` header line declares the dimensions instead of
+    /// inferring them from the rows. Shorter rows are padded with blocked cells. Lowercase
+    /// letters are free cells with a score of 0 that belong to the zone of that letter, uppercase
+    /// letters are pre-placed pieces like in the `Display` output. A header with more than
+    /// `MAX_CELLS` cells is rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines: Vec<&str> = s.split_terminator('\n').collect();
+
+        let (width, height) = match lines.first().and_then(|line| line.strip_prefix("FIELD ")) {
+            Some(header) => {
+                let mut dimensions = header.trim().splitn(2, 'x').map(str::parse::<usize>);
+                let (width, height) = match (dimensions.next(), dimensions.next()) {
+                    (Some(Ok(width)), Some(Ok(height)))
+                        if width
+                            .checked_mul(height)
+                            .is_some_and(|cells| cells <= MAX_CELLS) =>
+                    {
+                        (width, height)
+                    }
+                    _ => return Err(ParseError::InvalidHeader),
+                };
+                lines.remove(0);
+
+                if lines.len() > height || lines.iter().any(|line| line.chars().count() > width) {
+                    return Err(ParseError::DimensionMismatch);
+                }
+
+                (width, height)
+            }
+            None => (
+                lines.iter().fold(0, |a, b| a.max(b.chars().count())),
+                lines.len(),
+            ),
+        };
+
+        let mut result = Field {
+            width,
+            height,
+            field: vec![FieldState::Blocked; width * height],
+            zones: BTreeMap::new(),
+        };
+
+        for line in lines.iter().enumerate() {
+            for element in line.1.chars().enumerate() {
+                result.field[line.0 * width + element.0] = match element.1 {
+                    ' ' => FieldState::Blocked,
+                    '-' => FieldState::Free(0),
+                    e @ '1'..='9' => FieldState::Free(i16::from(e as u8 - b'1' + 1)),
+                    id @ 'A'..='Z' => FieldState::Occupied(id as u8 - b'A'),
+                    tag @ 'a'..='z' => {
+                        let cell = (element.0, line.0);
+                        result.zones.entry(tag).or_default().push(cell);
+                        FieldState::Free(0)
+                    }
+                    c if !c.is_ascii() => return Err(ParseError::NonAscii(c)),
+                    c => return Err(ParseError::UnexpectedCharacter(c)),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.width == 0 {
+            return Ok(());
+        }
+
+        // the tags of the zones by cell, most fields don't have any
+        let tags = if self.zones.is_empty() {
+            None
+        } else {
+            let mut tags = vec![None; self.field.len()];
+            for (&tag, cells) in self.zones.iter() {
+                for &(x, y) in cells {
+                    tags[x + y * self.width] = Some(tag);
+                }
+            }
+            Some(tags)
+        };
+
+        // one write per row instead of one per cell
+        let mut row = String::with_capacity(self.width + 1);
+        for (y, cells) in self.field.chunks(self.width).enumerate() {
+            row.clear();
+            if y != 0 {
+                row.push('\n');
+            }
+            for (x, state) in cells.iter().enumerate() {
+                let tag = match (state, &tags) {
+                    (&FieldState::Free(0), Some(tags)) => tags[x + y * self.width],
+                    _ => None,
+                };
+                row.push(tag.unwrap_or_else(|| state.to_char()));
+            }
+            f.write_str(&row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(s: &str) -> Field {
+        s.parse().unwrap()
+    }
+
+    fn piece(s: &str, id: u8) -> Piece {
+        let mut piece: Piece = s.parse().unwrap();
+        piece.id = id;
+        piece
+    }
+
+    #[test]
+    fn best_single_placement_covers_the_most_score() {
+        let start = field("1-9-\n--8-\n----");
+        let (board, covered) = start.best_single_placement(&piece("XX", 0)).unwrap();
+        assert_eq!(covered, 17);
+        assert_eq!(board.to_string(), "1-A-\n--A-\n----");
+        assert!(field("-").best_single_placement(&piece("XX", 0)).is_none());
+    }
+
+    #[test]
+    fn header_declares_the_dimensions() {
+        let padded = field("FIELD 5x4\n--\n-1-");
+        assert_eq!((padded.width, padded.height), (5, 4));
+        assert_eq!(padded.to_string(), "--   \n-1-  \n     \n     ");
+    }
+
+    #[test]
+    fn header_rejects_invalid_dimensions() {
+        let parse = |s: &str| s.parse::<Field>().err();
+        assert!(matches!(
+            parse("FIELD 2x2\n---"),
+            Some(ParseError::DimensionMismatch)
+        ));
+        assert!(matches!(
+            parse("FIELD 2x1\n--\n-"),
+            Some(ParseError::DimensionMismatch)
+        ));
+        assert!(matches!(
+            parse("FIELD 2x\n--"),
+            Some(ParseError::InvalidHeader)
+        ));
+        assert!(matches!(
+            parse("FIELD 20000x20000\n-"),
+            Some(ParseError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn with_piece_placed_matches_place_iter() {
+        let start = field("1-2\n-3-");
+        let corner = piece("XX\n X", 5);
+        assert_eq!(start.place_iter(&corner).count(), 2);
+        for (placed, (x, y)) in start.place_iter(&corner).with_positions() {
+            assert_eq!(start.with_piece_placed(&corner, x, y), Some(placed));
+        }
+        assert!(start.with_piece_placed(&corner, 2, 0).is_none());
+    }
+
+    #[test]
+    fn with_piece_placed_only_needs_the_occupied_cells_to_be_free() {
+        let corner = piece("XX\n X", 5);
+        assert!(field(" --\n---").with_piece_placed(&corner, 0, 0).is_none());
+        assert!(field("- -\n---").with_piece_placed(&corner, 0, 0).is_none());
+        assert!(field("-- \n---").with_piece_placed(&corner, 0, 0).is_some());
+    }
+
+    #[test]
+    fn highlight_diff_marks_the_changed_cells() {
+        let start = field("----\n----");
+        let placed = |y| {
+            start
+                .with_piece_placed(&piece("XX", 0), 0, 0)
+                .and_then(|field| field.with_piece_placed(&piece("XX", 1), 2, y))
+                .unwrap()
+        };
+        assert_eq!(
+            highlight_diff(&placed(0), &placed(1)).unwrap(),
+            "AAbb\n--**"
+        );
+        assert!(highlight_diff(&placed(0), &field("---")).is_err());
+    }
+
+    #[test]
+    fn uncoverable_free_cells_lists_cells_no_piece_reaches() {
+        let start = field("-- -\n--  \n   -");
+        assert_eq!(
+            start.uncoverable_free_cells(&[piece("XX", 0)]),
+            vec![(3, 0), (3, 2)]
+        );
+    }
+
+    #[test]
+    fn block_rect_and_free_rect_are_clipped_to_the_field() {
+        let mut edited = field("----\n----\n----");
+        edited.block_rect(1, 1, 2, 1);
+        assert_eq!(edited.to_string(), "----\n-  -\n----");
+        edited.free_rect(2, 1, 10, 10, 3);
+        assert_eq!(edited.to_string(), "----\n- 33\n--33");
+        edited.block_rect(5, 5, 2, 2);
+        edited.block_rect(3, 0, usize::MAX, usize::MAX);
+        assert_eq!(edited.to_string(), "--- \n- 3 \n--3 ");
+    }
+
+    #[test]
+    fn occupied_bounds_encloses_the_placed_pieces() {
+        let start = field("----\n----\n----");
+        assert_eq!(start.occupied_bounds(), None);
+        let placed = start.with_piece_placed(&piece(" X\nXX", 0), 2, 1).unwrap();
+        assert_eq!(placed.occupied_bounds(), Some((2, 1, 3, 2)));
+    }
+
+    #[test]
+    fn display_output_parses_back_to_the_same_field() {
+        for input in [
+            "-",
+            "1-\n-----\n\n 9",
+            "- \n  ",
+            "FIELD 3x2\n-",
+            "FIELD 0x0\n",
+            "\n\n-",
+        ] {
+            let parsed = field(input);
+            let reparsed = field(&parsed.to_string());
+            assert_eq!(parsed.field, reparsed.field, "{:?}", input);
+            if !parsed.field.is_empty() {
+                assert_eq!(parsed.width, reparsed.width, "{:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn header_rejects_overflowing_dimensions() {
+        let header = format!("FIELD {}x2\n-", usize::MAX);
+        assert!(matches!(
+            header.parse::<Field>(),
+            Err(ParseError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn partition_into_independent_splits_unconnected_regions() {
+        let parts = field("-- -\n-- 3\n  --").partition_into_independent();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].to_string(), "--  \n--  \n    ");
+        assert_eq!(parts[1].to_string(), "   -\n   3\n  --");
+    }
+
+    #[test]
+    fn placements_covering_only_yields_placements_over_the_cell() {
+        let start = field("---\n---");
+        let domino = piece("XX", 3);
+        let covering: Vec<Field> = start.placements_covering(&domino, (1, 1)).collect();
+        assert_eq!(covering.len(), 2);
+        assert!(covering
+            .iter()
+            .all(|placed| placed.field[4] == FieldState::Occupied(3)));
+        assert_eq!(start.placements_covering(&domino, (5, 0)).count(), 0);
+        assert_eq!(start.placements_covering(&domino, (0, 2)).count(), 0);
+        assert_eq!(
+            start.placements_covering(&domino, (0, usize::MAX)).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn to_boxed_string_draws_lines_between_pieces() {
+        let placed = field("---\n---")
+            .with_piece_placed(&piece("XX\nX", 0), 0, 0)
+            .and_then(|field| field.with_piece_placed(&piece("X\nX", 1), 2, 0))
+            .unwrap();
+        let expected = "\
++---+---+---+
+| A   A | B |
++   +---+   +
+| A | - | B |
++---+---+---+";
+        assert_eq!(placed.to_boxed_string(), expected);
+    }
+
+    #[test]
+    fn piece_at_returns_the_id_of_occupied_cells() {
+        let placed = field("-- \n---")
+            .with_piece_placed(&piece("XX", 4), 0, 1)
+            .unwrap();
+        assert_eq!(placed.piece_at(1, 1), Some(4));
+        assert_eq!(placed.piece_at(0, 0), None);
+        assert_eq!(placed.piece_at(2, 0), None);
+        assert_eq!(placed.piece_at(3, 0), None);
+        assert_eq!(placed.piece_at(0, 2), None);
+    }
+
+    #[test]
+    fn validate_against_accepts_placed_pieces_only() {
+        let start = field("--\n- ");
+        let placed = start.with_piece_placed(&piece("XX", 0), 0, 0).unwrap();
+        assert!(placed.validate().is_ok());
+        assert!(placed.validate_against(&start).is_ok());
+
+        let mut extra_cell = placed.clone();
+        extra_cell.field.push(FieldState::Blocked);
+        assert!(extra_cell.validate().is_err());
+
+        let mut covered_blocked = placed.clone();
+        covered_blocked.field[3] = FieldState::Occupied(1);
+        assert_eq!(
+            covered_blocked.validate_against(&start),
+            Err("cell 1/1 changed from Blocked to Occupied(1)".to_string())
+        );
+
+        let mut changed_score = placed;
+        changed_score.field[2] = FieldState::Free(3);
+        assert!(changed_score.validate_against(&start).is_err());
+    }
+
+    #[test]
+    fn to_padded_string_pads_the_rows_with_blocked_cells() {
+        let ragged = field("--\n-3-");
+        assert_eq!(ragged.to_padded_string(5), "--   \n-3-  ");
+        assert_eq!(ragged.to_padded_string(1), ragged.to_string());
+    }
+
+    #[test]
+    fn placement_coverage_counts_the_covered_cells() {
+        let start = field("----\n--- ");
+        let corner = piece("XX\n X", 0);
+        assert_eq!(start.placement_coverage(&corner, 0, 0), Some(3));
+        assert_eq!(start.placement_coverage(&corner, 2, 0), None);
+        assert_eq!(start.placement_coverage(&corner, 3, 0), None);
+    }
+
+    #[test]
+    fn apply_score_overlay_sets_scores_of_free_cells() {
+        let mut start = field("---");
+        start.apply_score_overlay("-3 . 12").unwrap();
+        assert_eq!(
+            start.field,
+            vec![
+                FieldState::Free(-3),
+                FieldState::Free(0),
+                FieldState::Free(12)
+            ]
+        );
+        assert_eq!(start.count(), 9);
+        // scores that don't fit into one character keep the grid intact
+        assert_eq!(start.to_string(), "?-?");
+    }
+
+    #[test]
+    fn apply_score_overlay_rejects_invalid_overlays() {
+        assert!(matches!(
+            field("- -").apply_score_overlay(". 3"),
+            Err(ParseError::InvalidScore(_))
+        ));
+        assert!(matches!(
+            field("--").apply_score_overlay("x"),
+            Err(ParseError::InvalidScore(_))
+        ));
+        assert!(matches!(
+            field("--").apply_score_overlay("1 2 3"),
+            Err(ParseError::DimensionMismatch)
+        ));
+    }
+
+    #[test]
+    fn extract_piece_recovers_the_placed_variant() {
+        let original = piece("XX\n X\n X", 3);
+        let variant = &original.all_variants()[2];
+        let placed = field("----\n----\n----")
+            .with_piece_placed(variant, 1, 0)
+            .unwrap();
+        let extracted = placed.extract_piece(3).unwrap();
+        assert_eq!(extracted.id, 3);
+        assert!(&extracted == variant);
+        assert!(extracted.canonical() == original.canonical());
+        assert!(placed.extract_piece(0).is_none());
+    }
+
+    #[test]
+    fn non_ascii_characters_are_reported_as_such() {
+        assert!(matches!(
+            "--é-\n----".parse::<Field>(),
+            Err(ParseError::NonAscii('é'))
+        ));
+        assert!(matches!(
+            "FIELD 2x1\né".parse::<Field>(),
+            Err(ParseError::NonAscii('é'))
+        ));
+    }
+
+    #[test]
+    fn placements_no_orphan_skips_placements_isolating_a_cell() {
+        let domino = piece("XX", 0);
+        assert_eq!(field("---\n---").placements_no_orphan(&domino).count(), 4);
+
+        let start = field("---\n-- ");
+        let all: Vec<Field> = start.place_iter(&domino).collect();
+        let no_orphan: Vec<Field> = start.placements_no_orphan(&domino).collect();
+        assert_eq!(all.len(), 3);
+        // the placement at 0/0 leaves 2/0 without free neighbours
+        assert_eq!(no_orphan, all[1..].to_vec());
+    }
+
+    #[test]
+    fn count_placements_sums_over_all_variants() {
+        let start = field("  -2-\n 4---5\n-------\n1-----3");
+        for shape in ["XX\n XX\n X", "XXXXXXXX", "X", "XX\nX"] {
+            let tested = piece(shape, 0);
+            let expected: usize = tested
+                .all_variants()
+                .iter()
+                .map(|variant| start.place_iter(variant).count())
+                .sum();
+            assert_eq!(start.count_placements(&tested), expected);
+        }
+    }
+
+    #[test]
+    fn can_place_checks_the_covered_cells() {
+        let start = field("  -2-\n 4---5\n-------\n1-----3");
+        assert!(start.can_place(&piece("X", 0), 2, 0));
+        assert!(!start.can_place(&piece("X", 0), 0, 0));
+        assert!(!start.can_place(&piece("XX", 0), 6, 3));
+    }
+
+    #[test]
+    fn render_svg_draws_a_rect_per_cell() {
+        let placed = field(" 2-\n---")
+            .with_piece_placed(&piece("XX", 1), 0, 1)
+            .unwrap();
+        let svg = render_svg(&placed, 10);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("width=\"30\" height=\"20\""));
+        assert_eq!(svg.matches("<rect").count(), 6);
+        assert_eq!(svg.matches("<text").count(), 1);
+        assert!(svg.contains(">2</text>"));
+    }
+
+    #[test]
+    fn edge_contact_counts_borders_and_neighbours() {
+        let corner = field("---\n---")
+            .with_piece_placed(&piece("X", 0), 0, 0)
+            .unwrap();
+        assert_eq!(corner.edge_contact(), 2);
+        let side = field("---\n---")
+            .with_piece_placed(&piece("X", 0), 1, 0)
+            .unwrap();
+        assert_eq!(side.edge_contact(), 1);
+        let neighbours = field(" --")
+            .with_piece_placed(&piece("X", 0), 1, 0)
+            .and_then(|field| field.with_piece_placed(&piece("X", 1), 2, 0))
+            .unwrap();
+        assert_eq!(neighbours.edge_contact(), 7);
+    }
+
+    #[test]
+    fn zones_are_free_cells_that_keep_their_tag() {
+        let start = field("ab-\n-a2");
+        assert_eq!(start.zone_cells('a'), &[(0, 0), (1, 1)]);
+        assert_eq!(start.zone_cells('b'), &[(1, 0)]);
+        assert!(start.zone_cells('c').is_empty());
+        assert_eq!(start.count(), 2);
+        assert_eq!(start.to_string(), "ab-\n-a2");
+
+        let placed = start.with_piece_placed(&piece("XX", 0), 0, 0).unwrap();
+        assert_eq!(placed.zone_cells('a'), start.zone_cells('a'));
+        assert_eq!(placed.to_string(), "AA-\n-a2");
+    }
+
+    #[test]
+    fn place_iter_allow_overhang_ignores_free_borders_of_the_piece() {
+        let padded: Piece = "XX \nX  \n   ".parse().unwrap();
+        let start = field("--\n--");
+        assert_eq!(start.place_iter(&padded).count(), 0);
+        let placed: Vec<Field> = start.place_iter_allow_overhang(&padded).collect();
+        assert_eq!(placed, vec![field("AA\nA-")]);
+    }
+
+    #[test]
+    fn apply_all_places_everything_or_nothing() {
+        let mut board = field("---\n---");
+        assert_eq!(
+            board.apply_all(&[(piece("XX", 0), 0, 0), (piece("X", 1), 2, 0)]),
+            Ok(())
+        );
+        assert_eq!(board, field("AAB\n---"));
+
+        let before = board.clone();
+        let overlapping = [
+            (piece("X", 2), 0, 1),
+            (piece("X", 3), 1, 1),
+            (piece("XX", 4), 1, 1),
+        ];
+        assert_eq!(board.apply_all(&overlapping), Err(2));
+        assert_eq!(board, before);
+        assert_eq!(board.apply_all(&[]), Ok(()));
+    }
+
+    #[test]
+    fn score_by_piece_sums_the_covered_scores() {
+        let start = field("12-\n-34");
+        let placed = start.with_piece_placed(&piece("XX", 5), 0, 0).unwrap();
+        let scores = placed.score_by_piece(&start);
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[&5], 3);
+    }
+
+    #[test]
+    fn render_catalog_svg_lays_the_pieces_out_in_a_grid() {
+        let pieces = [
+            piece("XX\nX", 0),
+            piece("XXXX", 1),
+            piece("X", 2),
+            piece("XX\nXX", 3),
+            piece("X\nXX\n X", 4),
+        ];
+        let layout = CatalogLayout::new(&pieces, 2);
+        assert_eq!((layout.cols, layout.rows), (2, 3));
+        assert_eq!((layout.slot_width, layout.slot_height), (3, 6));
+
+        let svg = render_catalog_svg(&pieces, 10, 2);
+        assert!(svg.contains("width=\"60\" height=\"180\""));
+        assert_eq!(svg.matches("<rect").count(), 3 + 4 + 1 + 4 + 4);
+        assert_eq!(svg.matches("Piece ").count(), 5);
+        assert!(svg.contains(">Piece E</text>"));
+    }
+
+    #[test]
+    fn layered_field_stacks_pieces_up_to_max_overlap() {
+        let mut layered = LayeredField::new(field("1-2\n- -"), 2);
+        assert!(layered.try_place(&piece("XX", 0), 0, 0));
+        assert!(layered.try_place(&piece("XX", 1), 1, 0));
+        assert_eq!(layered.to_string(), "ABB\n- -");
+        assert_eq!(layered.depth, vec![1, 2, 1, 0, 0, 0]);
+        assert!(!layered.can_place(&piece("X", 2), 1, 0));
+        assert!(layered.can_place(&piece("X", 2), 0, 0));
+        assert!(!layered.can_place(&piece("X", 2), 1, 1));
+    }
+
+    #[test]
+    fn layered_field_without_overlap_places_like_field() {
+        let start = field("1-2\n- -");
+        let domino = piece("XX", 0);
+        let layered: Vec<String> = LayeredField::new(start.clone(), 1)
+            .place_iter(&domino)
+            .map(|placed| placed.to_string())
+            .collect();
+        let plain: Vec<String> = start
+            .place_iter(&domino)
+            .map(|placed| placed.to_string())
+            .collect();
+        assert_eq!(layered, plain);
+    }
+
+    #[test]
+    fn free_components_uses_the_connectivity() {
+        let diagonal = field("- \n -");
+        assert_eq!(diagonal.free_components(Connectivity::Four).len(), 2);
+        assert_eq!(diagonal.free_components(Connectivity::Eight).len(), 1);
+    }
+
+    #[test]
+    fn from_str_hashblocked_reads_hashes_as_blocked_cells() {
+        let hashed = Field::from_str_hashblocked("#-1\n--#\n").unwrap();
+        assert_eq!(hashed, field(" -1\n-- "));
+        assert_eq!(
+            Field::from_str_hashblocked("FIELD 3x2\n-#-\n---").unwrap(),
+            field("FIELD 3x2\n- -\n---")
+        );
+    }
+
+    #[test]
+    fn from_str_hashblocked_rejects_spaces_and_ragged_rows() {
+        assert!(matches!(
+            Field::from_str_hashblocked("-- \n---"),
+            Err(ParseError::UnexpectedCharacter(' '))
+        ));
+        assert!(matches!(
+            Field::from_str_hashblocked("--\n---"),
+            Err(ParseError::DimensionMismatch)
+        ));
+    }
+
+    #[test]
+    fn fill_percent_is_the_covered_share_of_the_free_cells() {
+        let start = field("-- \n--");
+        let placed = start.with_piece_placed(&piece("XX", 0), 0, 0).unwrap();
+        assert_eq!(placed.fill_percent(&start), 0.5);
+        assert_eq!(start.fill_percent(&start), 0.0);
+        assert_eq!(field(" ").fill_percent(&field(" ")), 0.0);
+    }
+
+    #[test]
+    fn display_writes_the_tags_of_zones_into_free_cells() {
+        let tagged = field("  -12ab\n9-a--  \n-");
+        assert_eq!(tagged.to_string(), "  -12ab\n9-a--  \n-      ");
+
+        let mut scored = tagged.clone();
+        scored.field[3] = FieldState::Free(-4);
+        scored.field[8] = FieldState::Occupied(2);
+        // a tagged cell only shows its tag while it is free without a score
+        scored.field[5] = FieldState::Free(3);
+        assert_eq!(scored.to_string(), "  -?23b\n9Ca--  \n-      ");
+    }
+
+    #[test]
+    fn display_of_fields_without_zones() {
+        assert_eq!(field("-").to_string(), "-");
+        assert_eq!(field(" ").to_string(), " ");
+        assert_eq!(field("--\n-1").to_string(), "--\n-1");
+        let empty = Field {
+            width: 0,
+            height: 0,
+            field: vec![],
+            zones: BTreeMap::new(),
+        };
+        assert_eq!(empty.to_string(), "");
+    }
+
+    #[test]
+    fn must_touch_border_rejects_placements_in_the_interior() {
+        let start = field("---\n---\n---");
+        let mut dot = piece("X", 0);
+        dot.must_touch_border = true;
+        assert!(!start.can_place(&dot, 1, 1));
+        assert!(start.can_place(&dot, 0, 1));
+        assert_eq!(start.place_iter(&dot).count(), 8);
+    }
+
+    #[test]
+    fn placements_on_min_score_only_cover_high_scores() {
+        let start = field("1595\n5599\n----");
+        let domino = piece("XX", 0);
+        let mut count = 0;
+        for variant in domino.all_variants() {
+            for board in start.placements_on_min_score(&variant, 5) {
+                for (placed, original) in board.field.iter().zip(&start.field) {
+                    if placed != original {
+                        assert!(matches!(original, FieldState::Free(score) if *score >= 5));
+                    }
+                }
+                count += 1;
+            }
+        }
+        // 2 horizontal placements in the first row, 3 in the second and 3 vertical ones
+        assert_eq!(count, 2 + 3 + 3);
+        assert_eq!(
+            start.placements_on_min_score(&domino, 0).count(),
+            start.place_iter(&domino).count()
+        );
+    }
+
+    #[test]
+    fn highlight_diff_handles_ids_beyond_z() {
+        let start = field("---");
+        let placed = start
+            .with_piece_placed(&piece("X", 26), 0, 0)
+            .and_then(|field| field.with_piece_placed(&piece("X", 199), 1, 0))
+            .and_then(|field| field.with_piece_placed(&piece("X", 200), 2, 0))
+            .unwrap();
+        // `[` and `ĉ` have no lower case, `Ĉ` does
+        assert_eq!(highlight_diff(&placed, &start).unwrap(), "+ĉ+");
+    }
+
+    #[test]
+    fn can_place_rejects_coordinates_that_overflow() {
+        let start = field("--\n--");
+        assert!(!start.can_place(&piece("XX", 0), usize::MAX, 0));
+        assert!(!start.can_place(&piece("X\nX", 0), 0, usize::MAX));
+    }
+
+    #[test]
+    fn layered_field_keeps_pieces_of_the_start_field() {
+        let layered = LayeredField::new(field("A-\n--"), 2);
+        assert!(!layered.can_place(&piece("X", 1), 0, 0));
+        assert!(layered.can_place(&piece("X", 1), 1, 0));
+        assert!(!layered.can_place(&piece("XX", 1), usize::MAX, 0));
+        assert!(!layered.can_place(&piece("X\nX", 1), 0, usize::MAX));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::error::ParseError;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+/// Puzzle files are tiny, anything that inflates to more than this is rejected instead of
+/// exhausting the memory.
+const MAX_OUTPUT: usize = 16 << 20;
+
+/// CRC-32 as used by the gzip trailer.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, count: u32) -> Result<u32, ParseError> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or(ParseError::InvalidGzip)?;
+            value |= u32::from((byte >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, stored as the number of codes per length and the sorted symbols.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for i in 1..16 {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ParseError> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in self.counts[1..].iter() {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(ParseError::InvalidGzip)
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ParseError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER[..code_length_count].iter() {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.last().ok_or(ParseError::InvalidGzip)?,
+                3 + reader.bits(2)?,
+            ),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+
+    if lengths.len() != literal_count + distance_count {
+        return Err(ParseError::InvalidGzip);
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+/// Decodes a raw deflate stream of at most `limit` bytes, returning the output and the number of
+/// consumed bytes.
+fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize), ParseError> {
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+    let mut out: Vec<u8> = vec![];
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data
+                    .get(reader.pos..reader.pos + 4)
+                    .ok_or(ParseError::InvalidGzip)?;
+                let len = usize::from(u16::from_le_bytes([header[0], header[1]]));
+                let start = reader.pos + 4;
+                if out.len() + len > limit {
+                    return Err(ParseError::InvalidGzip);
+                }
+                out.extend_from_slice(
+                    data.get(start..start + len)
+                        .ok_or(ParseError::InvalidGzip)?,
+                );
+                reader.pos = start + len;
+            }
+            kind @ 1..=2 => {
+                let (literals, distances) = if kind == 1 {
+                    fixed_codes()
+                } else {
+                    dynamic_codes(&mut reader)?
+                };
+
+                loop {
+                    let symbol = usize::from(literals.decode(&mut reader)?);
+                    if symbol < 256 {
+                        if out.len() == limit {
+                            return Err(ParseError::InvalidGzip);
+                        }
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+
+                    let i = symbol - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(ParseError::InvalidGzip);
+                    }
+                    let len = usize::from(LENGTH_BASE[i])
+                        + reader.bits(u32::from(LENGTH_EXTRA[i]))? as usize;
+
+                    let i = usize::from(distances.decode(&mut reader)?);
+                    if i >= DISTANCE_BASE.len() {
+                        return Err(ParseError::InvalidGzip);
+                    }
+                    let distance = usize::from(DISTANCE_BASE[i])
+                        + reader.bits(u32::from(DISTANCE_EXTRA[i]))? as usize;
+
+                    if distance > out.len() || out.len() + len > limit {
+                        return Err(ParseError::InvalidGzip);
+                    }
+                    for _ in 0..len {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+            _ => return Err(ParseError::InvalidGzip),
+        }
+
+        if last {
+            reader.align();
+            return Ok((out, reader.pos));
+        }
+    }
+}
+
+/// Decompresses a single member gzip file and verifies its checksum and size.
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>, ParseError> {
+    if data.len() < 18 || data[0..3] != [0x1f, 0x8b, 8] {
+        return Err(ParseError::InvalidGzip);
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 0x04 != 0 {
+        let extra = data.get(pos..pos + 2).ok_or(ParseError::InvalidGzip)?;
+        pos += 2 + usize::from(u16::from_le_bytes([extra[0], extra[1]]));
+    }
+    for flag in [0x08, 0x10].iter() {
+        if flags & flag != 0 {
+            let rest = data.get(pos..).ok_or(ParseError::InvalidGzip)?;
+            pos += rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(ParseError::InvalidGzip)?
+                + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+
+    let (out, consumed) = inflate(data.get(pos..).ok_or(ParseError::InvalidGzip)?, MAX_OUTPUT)?;
+
+    let trailer = data
+        .get(pos + consumed..pos + consumed + 8)
+        .ok_or(ParseError::InvalidGzip)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err(ParseError::InvalidGzip);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wraps a raw deflate stream into a gzip member without optional header fields.
+    fn member(deflated: &[u8], original: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+        data.extend_from_slice(deflated);
+        data.extend_from_slice(&crc32(original).to_le_bytes());
+        data.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        data
+    }
+
+    /// A field, compressed by zlib into a single dynamic block.
+    fn dynamic_block() -> (Vec<u8>, Vec<u8>) {
+        let original: String = (0..40)
+            .map(|i| format!("{}-{}\n", i % 10, "-".repeat(i % 7)))
+            .collect();
+        let deflated = vec![
+            53, 142, 201, 17, 192, 48, 8, 3, 255, 244, 162, 25, 95, 73, 236, 254, 27, 139, 145,
+            196, 7, 24, 163, 101, 221, 16, 29, 136, 129, 91, 38, 178, 46, 176, 61, 80, 127, 225,
+            225, 67, 236, 219, 78, 206, 141, 47, 93, 139, 225, 192, 172, 228, 34, 77, 242, 82, 89,
+            183, 22, 199, 129, 86, 201, 78, 154, 164, 188, 214, 150, 85, 184, 46, 36, 77, 82, 94,
+            107, 203, 234, 111, 243, 66, 210, 36, 229, 181, 54, 126,
+        ];
+        (deflated, original.into_bytes())
+    }
+
+    #[test]
+    fn crc32_matches_the_reference() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"ab"), 0x9e83_486d);
+    }
+
+    #[test]
+    fn decompresses_stored_blocks() {
+        let data = member(&[1, 2, 0, 253, 255, b'a', b'b'], b"ab");
+        assert_eq!(decompress(&data).unwrap(), b"ab");
+    }
+
+    #[test]
+    fn decompresses_fixed_blocks() {
+        let deflated = [75, 76, 74, 78, 4, 35, 46, 0];
+        let data = member(&deflated, b"abcabcabc\n");
+        assert_eq!(decompress(&data).unwrap(), b"abcabcabc\n");
+    }
+
+    #[test]
+    fn decompresses_dynamic_blocks() {
+        let (deflated, original) = dynamic_block();
+        assert_eq!(decompress(&member(&deflated, &original)).unwrap(), original);
+    }
+
+    #[test]
+    fn rejects_corrupted_data() {
+        let (deflated, original) = dynamic_block();
+        let mut data = member(&deflated, &original);
+        let crc = data.len() - 8;
+        data[crc] ^= 1;
+        assert!(matches!(decompress(&data), Err(ParseError::InvalidGzip)));
+        assert!(matches!(decompress(b"nope"), Err(ParseError::InvalidGzip)));
+    }
+
+    #[test]
+    fn rejects_output_beyond_the_limit() {
+        let (deflated, original) = dynamic_block();
+        assert!(inflate(&deflated, original.len()).is_ok());
+        assert!(matches!(
+            inflate(&deflated, original.len() - 1),
+            Err(ParseError::InvalidGzip)
+        ));
+        assert!(matches!(
+            inflate(&[1, 2, 0, 253, 255, b'a', b'b'], 1),
+            Err(ParseError::InvalidGzip)
+        ));
+    }
+}
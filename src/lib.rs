@@ -0,0 +1,24 @@
+mod error;
+mod field;
+#[cfg(feature = "gzip")]
+mod gzip;
+mod piece;
+#[cfg(feature = "image")]
+mod raster;
+mod solution;
+#[cfg(feature = "toml-subset")]
+mod toml_subset;
+
+pub use crate::error::ParseError;
+pub use crate::field::{
+    highlight_diff, render_catalog_svg, render_svg, Field, FieldState, LayeredField, PlaceIterator,
+};
+pub use crate::piece::{
+    congruent_pieces, load_pieces_from_dir, normalize_pieces, parse_piece_blocks, parse_pieces,
+    piece_char, piece_sets_equal, read_puzzle_file, Connectivity, Piece, PieceState, SymmetryGroup,
+};
+#[cfg(feature = "image")]
+pub use crate::raster::{render_catalog, RgbaImage};
+pub use crate::solution::{Objective, Solution, SolutionMetrics, SolveOptions, SolveStats};
+#[cfg(feature = "toml-subset")]
+pub use crate::toml_subset::load_puzzle_toml;
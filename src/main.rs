@@ -1,397 +1,373 @@
+mod output;
+mod repl;
+
+use std::error::Error;
 use std::fmt;
 use std::fs;
-use std::str::FromStr;
-
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
-enum PieceState {
-    Occupied,
-    Free,
-}
-
-#[derive(Clone, Eq, PartialEq, Hash)]
-struct Piece {
-    id: u8,
-    width: usize,
-    height: usize,
-    field: Vec<PieceState>,
+use std::io::{self, Write};
+use std::iter;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use crate::output::Format;
+
+use just_in_time_game::{
+    congruent_pieces, load_pieces_from_dir, parse_piece_blocks, piece_char, read_puzzle_file,
+    Field, ParseError, Piece, Solution,
+};
+
+/// Everything that can go wrong in `run`, printed by `main`.
+#[derive(Debug)]
+enum AppError {
+    Io(io::Error),
+    /// a file that couldn't be read or parsed, with its path
+    Parse(String, ParseError),
+    /// a manifest line that isn't `<field> <pieces>`
+    InvalidManifest(String),
+    /// ids of pieces that are already placed on the field
+    IdClash(Vec<u8>),
 }
 
-impl Piece {
-    fn all_variants(&self) -> Vec<Piece> {
-        use std::collections::HashSet;
-        let mut set = HashSet::new();
-
-        let transform = |set: &mut HashSet<Piece>, start: Piece| {
-            set.insert(start.flipped_horizontally());
-            let vert = start.flipped_vertically();
-            set.insert(vert.flipped_horizontally());
-            set.insert(vert);
-            set.insert(start);
-        };
-
-        transform(&mut set, self.clone());
-        transform(&mut set, self.transposed());
-
-        set.into_iter().collect()
-    }
-
-    fn flipped_horizontally(&self) -> Piece {
-        let mut t = self.clone();
-
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let src_y = self.height - y - 1;
-                t.field[x + y * self.width] = self.field[x + src_y * self.width].clone();
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AppError::Io(ref e) => write!(f, "{}", e),
+            AppError::Parse(ref path, ref e) => write!(f, "{}: {}", path, e),
+            AppError::InvalidManifest(ref line) => write!(f, "invalid manifest line {:?}", line),
+            AppError::IdClash(ref ids) => {
+                let names: Vec<String> = ids.iter().map(|&id| piece_char(id).to_string()).collect();
+                write!(
+                    f,
+                    "the field already contains the piece ids {}, use --remap-ids to rename the pieces",
+                    names.join(", ")
+                )
             }
         }
-
-        t
     }
+}
 
-    fn flipped_vertically(&self) -> Piece {
-        let mut t = self.clone();
-
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let src_x = self.width - x - 1;
-                t.field[x + y * self.width] = self.field[src_x + y * self.width].clone();
-            }
+impl Error for AppError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AppError::Io(ref e) => Some(e),
+            AppError::Parse(_, ref e) => Some(e),
+            AppError::InvalidManifest(_) | AppError::IdClash(_) => None,
         }
-
-        t
     }
+}
 
-    fn transposed(&self) -> Piece {
-        let mut t = Piece {
-            id: self.id,
-            width: self.height,
-            height: self.width,
-            field: vec![PieceState::Free; self.width * self.height],
-        };
-
-        for x in 0..t.width {
-            for y in 0..t.height {
-                t.field[x + y * t.width] = self.field[y + x * self.width].clone();
-            }
-        }
-
-        t
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> AppError {
+        AppError::Io(e)
     }
 }
 
-impl FromStr for Piece {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<&str> = s.split_terminator('\n').collect();
-
-        if lines.is_empty() {
-            return Err("id and at least one line are required");
-        }
-
-        let width = lines.iter().fold(0, |a, b| a.max(b.len()));
+/// The flags of a single puzzle run.
+#[derive(Clone, Copy, Debug)]
+struct Options {
+    sort_by_score: bool,
+    count_only: bool,
+    repl: bool,
+    /// renames pieces whose ids are already placed on the field instead of failing
+    remap_ids: bool,
+    format: Format,
+}
 
-        let mut result = Piece {
-            id: 0,
-            width,
-            height: lines.len(),
-            field: vec![PieceState::Free; width * lines.len()],
-        };
+fn print_boards<W: Write>(out: &mut W, solution: &Solution, sort_by_score: bool) -> io::Result<()> {
+    writeln!(out, "start:\n{}\n", solution.start)?;
 
-        for line in lines.iter().enumerate() {
-            for element in line.1.chars().enumerate() {
-                result.field[line.0 * width + element.0] = match element.1 {
-                    'X' => PieceState::Occupied,
-                    ' ' => PieceState::Free,
-                    _ => return Err("unexpected character"),
-                }
-            }
+    for piece in solution.pieces.iter() {
+        writeln!(out, "Pieces:")?;
+        writeln!(out, "Piece {}", piece_char(piece[0].id))?;
+        for p in piece.iter() {
+            writeln!(out, "{}\n", p)?;
         }
-
-        Ok(result)
+        writeln!(out)?;
     }
-}
 
-impl fmt::Display for Piece {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for e in self.field.iter().enumerate() {
-            if e.0 % self.width == 0 && e.0 != 0 {
-                writeln!(f)?;
+    writeln!(out, "Possible placements:")?;
+    for piece in solution.pieces.iter() {
+        for variant in piece.iter() {
+            for placement in solution.start.place_iter(variant) {
+                writeln!(out, "{}\n", placement)?;
             }
-            write!(
-                f,
-                "{}",
-                match *e.1 {
-                    PieceState::Free => " ",
-                    PieceState::Occupied => "X",
-                }
-            )?;
         }
-        Ok(())
     }
-}
 
-#[derive(Clone, Debug)]
-enum FieldState {
-    Blocked,
-    /// id of the element
-    Occupied(u8),
-    ///< score
-    Free(u8),
-}
-
-#[derive(Clone, Debug)]
-struct Field {
-    width: usize,
-    height: usize,
-    field: Vec<FieldState>,
-}
-
-impl Field {
-    fn place_iter<'a>(&'a self, piece: &'a Piece) -> PlaceIterator<'a> {
-        PlaceIterator {
-            field: self,
-            piece,
-            x: 0,
-            y: 0,
-        }
+    writeln!(out, "Solutions:")?;
+    let solutions = if sort_by_score {
+        solution.solutions_by_score()
+    } else {
+        solution.solutions.clone()
+    };
+    for s in solutions.iter() {
+        writeln!(out, "{}\n", s)?;
     }
 
-    fn count(&self) -> u8 {
-        self.field.iter().fold(0u8, |acc, field| {
-            acc + match *field {
-                FieldState::Free(score) => score,
-                _ => 0,
-            }
-        })
+    writeln!(out, "Best solutions")?;
+    for s in solution.best_solutions().iter() {
+        writeln!(out, "{}\n", s)?;
     }
+    Ok(())
 }
 
-struct PlaceIterator<'a> {
-    field: &'a Field,
-    piece: &'a Piece,
-    x: usize,
-    y: usize,
+fn load_field<P: AsRef<Path>>(filename: P) -> Result<Field, AppError> {
+    let error = |e| AppError::Parse(filename.as_ref().display().to_string(), e);
+    read_puzzle_file(&filename)
+        .map_err(error)?
+        .parse()
+        .map_err(error)
 }
 
-impl<'a> Iterator for PlaceIterator<'a> {
-    type Item = Field;
-    fn next(&mut self) -> Option<Self::Item> {
-        'main: loop {
-            let field_offset_x = self.x;
-            let field_offset_y = self.y;
-
-            if field_offset_y > self.field.height - self.piece.height {
-                return None; // and we're done
-            }
-
-            self.x += 1;
-
-            if self.x > self.field.width - self.piece.width {
-                self.x = 0;
-                self.y += 1;
-            }
-
-            let mut ret = self.field.clone();
-
-            for piece_x in 0..self.piece.width {
-                for piece_y in 0..self.piece.height {
-                    let field_x = field_offset_x + piece_x;
-                    let field_y = field_offset_y + piece_y;
-                    if self.piece.field[piece_x + piece_y * self.piece.width]
-                        == PieceState::Occupied
-                    {
-                        match ret.field[field_x + field_y * ret.width].clone() {
-                            FieldState::Free(_) => {
-                                ret.field[field_x + field_y * ret.width] =
-                                    FieldState::Occupied(self.piece.id)
-                            }
-                            _ => continue 'main,
-                        }
-                    }
-                }
-            }
-
-            return Some(ret);
+/// Reads a directory with one piece per file or a file with pieces separated by empty lines.
+/// Warns about pieces that are listed more than once, apart from the copies of an `x<count>`
+/// piece.
+fn load_pieces<P: AsRef<Path>>(filename: P) -> Result<Vec<Piece>, AppError> {
+    let error = |e| AppError::Parse(filename.as_ref().display().to_string(), e);
+    let blocks = if fs::metadata(&filename).is_ok_and(|m| m.is_dir()) {
+        let pieces = load_pieces_from_dir(&filename).map_err(error)?;
+        pieces.into_iter().map(|piece| vec![piece]).collect()
+    } else {
+        let content = read_puzzle_file(&filename).map_err(error)?;
+        parse_piece_blocks(&content).map_err(error)?
+    };
+
+    // the ids are assigned in order, so they index this
+    let block_of: Vec<usize> = blocks
+        .iter()
+        .enumerate()
+        .flat_map(|(i, block)| iter::repeat_n(i, block.len()))
+        .collect();
+    let pieces: Vec<Piece> = blocks.into_iter().flatten().collect();
+
+    for (a, b) in congruent_pieces(&pieces) {
+        if block_of[usize::from(a)] != block_of[usize::from(b)] {
+            eprintln!(
+                "warning: pieces {} and {} have the same shape",
+                piece_char(a),
+                piece_char(b)
+            );
         }
     }
-}
-
-impl FromStr for Field {
-    type Err = &'static str;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<&str> = s.split_terminator('\n').collect();
-        let width = lines.iter().fold(0, |a, b| a.max(b.len()));
+    Ok(pieces)
+}
 
-        let mut result = Field {
-            width,
-            height: lines.len(),
-            field: vec![FieldState::Blocked; width * lines.len()],
+/// Solves every `<field> <pieces>` line of `manifest` and prints one CSV row per puzzle. Relative
+/// paths are resolved against the directory of the manifest.
+fn run_batch<W: Write>(out: &mut W, manifest: &str) -> Result<(), AppError> {
+    let content =
+        read_puzzle_file(manifest).map_err(|e| AppError::Parse(manifest.to_string(), e))?;
+    let base = Path::new(manifest)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    writeln!(out, "field,pieces,solution_count,best_score,elapsed_ms")?;
+    for line in content.lines() {
+        let files: Vec<&str> = line.split_whitespace().collect();
+        let (field_filename, pieces_filename) = match files[..] {
+            [] => continue,
+            [field, pieces] => (field, pieces),
+            _ => return Err(AppError::InvalidManifest(line.to_string())),
         };
 
-        for line in lines.iter().enumerate() {
-            for element in line.1.chars().enumerate() {
-                result.field[line.0 * width + element.0] = match element.1 {
-                    ' ' => FieldState::Blocked,
-                    '-' => FieldState::Free(0),
-                    e @ '1'..='9' => FieldState::Free(e as u8 - b'1' + 1),
-                    _ => return Err("unexpected character"),
-                }
-            }
-        }
-
-        Ok(result)
+        let started = Instant::now();
+        let field = load_field(base.join(field_filename))?;
+        let pieces = load_pieces(base.join(pieces_filename))?;
+        let solution = Solution::new(&field, &pieces);
+
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            field_filename,
+            pieces_filename,
+            solution.solutions.len(),
+            solution.highest_score(),
+            started.elapsed().as_millis()
+        )?;
     }
-}
 
-impl fmt::Display for Field {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for e in self.field.iter().enumerate() {
-            if e.0 % self.width == 0 && e.0 != 0 {
-                writeln!(f)?;
-            }
-            match *e.1 {
-                FieldState::Blocked => write!(f, " "),
-                FieldState::Free(0) => write!(f, "-"),
-                FieldState::Free(n) => write!(f, "{}", n),
-                FieldState::Occupied(n) => write!(f, "{}", (b'A' + n) as char),
-            }?;
-        }
-        Ok(())
-    }
-}
-
-struct Solution {
-    start: Field,
-    pieces: Vec<Vec<Piece>>,
-    solutions: Vec<Field>,
+    Ok(())
 }
 
-impl Solution {
-    fn new(start: &Field, pieces: &[Piece]) -> Solution {
-        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
-        let mut solutions = vec![];
-        Solution::solve(start, &pieces, &mut solutions);
-
-        Solution {
-            start: start.clone(),
-            pieces,
-            solutions,
-        }
+/// Loads and solves a single puzzle and writes it to `out` in the format of `options`.
+fn run<W: Write>(
+    out: &mut W,
+    field_path: &str,
+    pieces_path: &str,
+    options: &Options,
+) -> Result<(), AppError> {
+    if options.format == Format::Plain && !options.count_only {
+        writeln!(out, "field={} pieces={}", field_path, pieces_path)?;
     }
 
-    fn solve(state: &Field, remaining_pieces: &[Vec<Piece>], solutions: &mut Vec<Field>) {
-        assert!(!remaining_pieces.is_empty());
-
-        let top = &remaining_pieces[0];
-        let rest = &remaining_pieces[1..];
-
-        for piece in top.iter() {
-            for placement in state.place_iter(&piece) {
-                if rest.is_empty() {
-                    solutions.push(placement);
-                } else {
-                    Solution::solve(&placement, &rest, solutions);
-                }
-            }
+    let field = load_field(field_path)?;
+    let mut pieces = load_pieces(pieces_path)?;
+    if let Err(ids) = Solution::check_piece_ids(&field, &pieces) {
+        if !options.remap_ids {
+            return Err(AppError::IdClash(ids));
         }
+        pieces = Solution::remap_piece_ids(&field, &pieces).ok_or(AppError::IdClash(ids))?;
     }
 
-    fn highest_score(&self) -> u8 {
-        self.solutions.iter().map(|f| f.count()).max().unwrap_or(0)
+    if options.repl {
+        return Ok(repl::run(field, pieces, io::stdin().lock(), out)?);
     }
 
-    fn best_solutions(&self) -> Vec<Field> {
-        let highest_score = self.highest_score();
-        self.solutions
-            .iter()
-            .filter(|field| field.count() == highest_score)
-            .cloned()
-            .collect()
+    let unplaceable = Solution::unplaceable_pieces(&field, &pieces);
+    if options.format == Format::Plain && !unplaceable.is_empty() {
+        for id in unplaceable {
+            writeln!(out, "Piece {} can't be placed anywhere", piece_char(id))?;
+        }
+        return Ok(());
     }
-}
 
-fn main() {
-    let mut args = std::env::args();
-
-    let app_name = args.nth(0).unwrap();
+    let solution = Solution::new(&field, &pieces[..]);
 
-    if args.len() < 2 {
-        println!("usage {} <field> <pieces>", app_name);
-        return;
+    if options.format != Format::Plain {
+        return Ok(output::print(out, &solution, options.format)?);
     }
 
-    let field_filename = args.nth(0).unwrap();
-    let pieces_filename = args.nth(0).unwrap();
+    if !options.count_only {
+        print_boards(out, &solution, options.sort_by_score)?;
+    }
 
-    println!("field={} pieces={}", field_filename, pieces_filename);
+    writeln!(out, "Number of solutions {}", solution.solutions.len())?;
+    writeln!(out, "Highest score {}", solution.highest_score())?;
+    Ok(())
+}
 
-    let field: Field = fs::read_to_string(field_filename)
-        .expect("couldn't read field file")
-        .parse()
-        .unwrap();
-
-    let mut pieces: Vec<Piece> = vec![];
-    {
-        let content = fs::read_to_string(pieces_filename).expect("couldn't open pieces file");
-
-        let mut current = vec![];
-        let mut id = 0u8;
-        for line in content.lines() {
-            if line.is_empty() {
-                if current.is_empty() {
-                    panic!("Pieces file contains two empty lines");
-                }
-                let mut piece: Piece = current.join("\n").parse().unwrap();
-                piece.id = id;
-                id += 1;
-                pieces.push(piece);
-                current = vec![];
-            } else {
-                current.push(line);
-            }
-        }
+/// Flags that don't take a value, `--format` is handled separately.
+const FLAGS: [&str; 5] = [
+    "--sort-by-score",
+    "--count-only",
+    "--repl",
+    "--remap-ids",
+    "--batch",
+];
+
+fn usage(app_name: &str) -> String {
+    format!(
+        "usage {} [--sort-by-score] [--count-only] [--repl] [--remap-ids] [--format <plain|json|jsonl|svg|diff>] <field> <pieces>\n      {} --batch <manifest>",
+        app_name, app_name
+    )
+}
 
-        if !current.is_empty() {
-            let mut piece: Piece = current.join("\n").parse().unwrap();
-            piece.id = id;
-            pieces.push(piece);
+fn main() {
+    let mut flags: Vec<String> = vec![];
+    let mut args: Vec<String> = vec![];
+    let mut format = Format::Plain;
+    let mut iter = std::env::args_os().map(|arg| {
+        arg.into_string().unwrap_or_else(|arg| {
+            eprintln!("invalid argument {:?}, only UTF-8 is supported", arg);
+            process::exit(2);
+        })
+    });
+    while let Some(arg) = iter.next() {
+        if arg == "--format" || arg.starts_with("--format=") {
+            let name = match arg.strip_prefix("--format=") {
+                Some(name) => name.to_string(),
+                None => iter.next().unwrap_or_default(),
+            };
+            format = Format::parse(&name).unwrap_or_else(|| {
+                eprintln!(
+                    "unknown format {:?}, expected plain, json, jsonl, svg or diff",
+                    name
+                );
+                process::exit(2);
+            });
+        } else if arg.starts_with("--") {
+            flags.push(arg);
+        } else {
+            args.push(arg);
         }
     }
 
-    let solution = Solution::new(&field, &pieces[..]);
-
-    println!("start:\n{}\n", solution.start);
+    let app_name = args.first().map_or("just_in_time_game", String::as_str);
+    if let Some(flag) = flags.iter().find(|flag| !FLAGS.contains(&flag.as_str())) {
+        eprintln!("unknown flag {}\n{}", flag, usage(app_name));
+        process::exit(2);
+    }
 
-    for piece in solution.pieces.iter() {
-        println!("Pieces:");
-        println!("Piece {}", (b'A' + piece[0].id) as char);
-        for p in piece.iter() {
-            println!("{}\n", p);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = if flags.iter().any(|flag| flag == "--batch") {
+        // the manifest is the only positional argument
+        if args.len() != 2 {
+            eprintln!("{}", usage(app_name));
+            process::exit(2);
         }
-        println!();
+        run_batch(&mut out, &args[1])
+    } else if args.len() < 3 {
+        eprintln!("{}", usage(app_name));
+        process::exit(2);
+    } else {
+        let options = Options {
+            sort_by_score: flags.iter().any(|flag| flag == "--sort-by-score"),
+            count_only: flags.iter().any(|flag| flag == "--count-only"),
+            repl: flags.iter().any(|flag| flag == "--repl"),
+            remap_ids: flags.iter().any(|flag| flag == "--remap-ids"),
+            format,
+        };
+        run(&mut out, &args[1], &args[2], &options)
+    };
+
+    if let Err(e) = result.and_then(|()| Ok(out.flush()?)) {
+        eprintln!("error: {}", e);
+        process::exit(1);
     }
+}
 
-    println!("Possible placements:");
-    for piece in solution.pieces.iter() {
-        for variant in piece.iter() {
-            for placement in solution.start.place_iter(&variant) {
-                println!("{}\n", placement);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(count_only: bool) -> Options {
+        Options {
+            sort_by_score: false,
+            count_only,
+            repl: false,
+            remap_ids: false,
+            format: Format::Plain,
         }
     }
 
-    println!("Solutions:");
-    for s in solution.solutions.iter() {
-        println!("{}\n", s);
+    #[test]
+    fn run_writes_the_summary() {
+        let mut out = vec![];
+        run(&mut out, "3/F1.txt", "3/P1.txt", &options(true)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Number of solutions 328\nHighest score 12\n"
+        );
     }
 
-    println!("Best solutions");
-    for s in solution.best_solutions().iter() {
-        println!("{}\n", s);
+    #[test]
+    fn run_reports_missing_files() {
+        let mut out = vec![];
+        let result = run(&mut out, "missing/field", "3/P1.txt", &options(false));
+        match result {
+            Err(AppError::Parse(path, ParseError::Io(_))) => assert_eq!(path, "missing/field"),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "field=missing/field pieces=3/P1.txt\n"
+        );
     }
 
-    println!("Number of solutions {}", solution.solutions.len());
-    println!("Highest score {}", solution.highest_score());
+    #[test]
+    fn run_batch_reports_invalid_manifest_lines() {
+        let dir =
+            std::env::temp_dir().join(format!("just_in_time_game_run_batch_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest");
+        fs::write(&manifest, "field\n").unwrap();
+
+        let mut out = vec![];
+        let result = run_batch(&mut out, manifest.to_str().unwrap());
+        assert!(matches!(result, Err(AppError::InvalidManifest(ref line)) if line == "field"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,92 @@
+use std::io::{self, Write};
+
+use just_in_time_game::{highlight_diff, render_svg, Field, Solution};
+
+/// How `main` prints the solutions, selected with `--format`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Plain,
+    Json,
+    JsonLines,
+    Svg,
+    Diff,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "plain" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "jsonl" => Some(Format::JsonLines),
+            "svg" => Some(Format::Svg),
+            "diff" => Some(Format::Diff),
+            _ => None,
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            c if (c as u32) < 0x20 => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// The rows of `field` as a JSON array of strings.
+fn json_board(field: &Field) -> String {
+    let rows: Vec<String> = field.to_string().lines().map(json_string).collect();
+    format!("[{}]", rows.join(","))
+}
+
+fn json_solution(field: &Field) -> String {
+    format!(
+        "{{\"board\":{},\"score\":{}}}",
+        json_board(field),
+        field.count()
+    )
+}
+
+/// Writes the solutions in `format`, everything but `Format::Plain`, which `main` handles.
+pub fn print<W: Write>(out: &mut W, solution: &Solution, format: Format) -> io::Result<()> {
+    match format {
+        Format::Plain => unreachable!("plain output is printed by main"),
+        Format::Json => {
+            let solutions: Vec<String> = solution.solutions.iter().map(json_solution).collect();
+            writeln!(
+                out,
+                "{{\"start\":{},\"solution_count\":{},\"highest_score\":{},\"solutions\":[{}]}}",
+                json_board(&solution.start),
+                solution.solutions.len(),
+                solution.highest_score(),
+                solutions.join(",")
+            )?;
+        }
+        Format::JsonLines => {
+            for field in solution.solutions.iter() {
+                writeln!(out, "{}", json_solution(field))?;
+            }
+        }
+        Format::Svg => {
+            // the start board if there is no solution, so there is always a document
+            let best = solution.best_solutions().into_iter().next();
+            let board = best.as_ref().unwrap_or(&solution.start);
+            write!(out, "{}", render_svg(board, 32))?;
+        }
+        Format::Diff => {
+            for field in solution.solutions.iter() {
+                // the solutions have the dimensions of the start
+                let diff = highlight_diff(field, &solution.start).unwrap();
+                writeln!(out, "{}\n", diff)?;
+            }
+        }
+    }
+    Ok(())
+}
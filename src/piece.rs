@@ -0,0 +1,1025 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::ParseError;
+
+/// Piece ids are a single byte.
+pub(crate) const MAX_PIECES: usize = 256;
+
+/// The character a piece id is written as, `A` for 0. Only `A` to `Z` can be read back, later
+/// ids continue with the following code points.
+pub fn piece_char(id: u8) -> char {
+    char::from_u32(u32::from(b'A') + u32::from(id)).unwrap()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PieceState {
+    Occupied,
+    /// an occupied cell that has to land on a particular kind of cell, written as `O`
+    Anchor,
+    Free,
+}
+
+impl PieceState {
+    pub fn is_occupied(&self) -> bool {
+        *self != PieceState::Free
+    }
+}
+
+/// The transforms that leave a piece unchanged, named after the dihedral subgroups.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum SymmetryGroup {
+    /// no symmetry
+    C1,
+    /// unchanged by a rotation by 180 degrees
+    C2,
+    /// unchanged by rotations by 90 degrees
+    C4,
+    /// a single mirror axis
+    D1,
+    /// two mirror axes and a rotation by 180 degrees
+    D2,
+    /// all 8 transforms
+    D4,
+}
+
+impl SymmetryGroup {
+    /// Number of transforms in the group.
+    pub fn order(self) -> usize {
+        match self {
+            SymmetryGroup::C1 => 1,
+            SymmetryGroup::C2 | SymmetryGroup::D1 => 2,
+            SymmetryGroup::C4 | SymmetryGroup::D2 => 4,
+            SymmetryGroup::D4 => 8,
+        }
+    }
+}
+
+/// Which cells count as touching in connectivity checks.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub enum Connectivity {
+    /// only cells sharing an edge
+    #[default]
+    Four,
+    /// cells sharing an edge or a corner
+    Eight,
+}
+
+impl Connectivity {
+    /// The neighbors of `x`/`y` inside a `width` x `height` grid.
+    pub(crate) fn neighbors(
+        self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Vec<(usize, usize)> {
+        let offsets: &[(isize, isize)] = match self {
+            Connectivity::Four => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        };
+        offsets
+            .iter()
+            .map(|&(dx, dy)| (x.wrapping_add(dx as usize), y.wrapping_add(dy as usize)))
+            .filter(|&(x, y)| x < width && y < height)
+            .collect()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct Piece {
+    pub id: u8,
+    /// bonus for placing the piece under `Objective::Weighted`
+    pub weight: i32,
+    /// the piece is placed like any other, but the objectives ignore it and its covered cells
+    pub decorative: bool,
+    /// placements must cover at least one cell on the border of the field
+    pub must_touch_border: bool,
+    pub width: usize,
+    pub height: usize,
+    pub field: Vec<PieceState>,
+}
+
+impl Piece {
+    pub fn all_variants(&self) -> Vec<Piece> {
+        let mut set = HashSet::new();
+
+        let transform = |set: &mut HashSet<Piece>, start: Piece| {
+            set.insert(start.flipped_horizontally());
+            let vert = start.flipped_vertically();
+            set.insert(vert.flipped_horizontally());
+            set.insert(vert);
+            set.insert(start);
+        };
+
+        transform(&mut set, self.clone());
+        transform(&mut set, self.transposed());
+
+        // sorted, so that variants can be referred to by their index
+        let mut variants: Vec<Piece> = set.into_iter().collect();
+        variants.sort_by_cached_key(Piece::shape_key);
+        variants
+    }
+
+    pub fn occupied_count(&self) -> usize {
+        self.field
+            .iter()
+            .filter(|state| state.is_occupied())
+            .count()
+    }
+
+    /// Mean of the centers of the occupied cells, with the top left corner of the piece at 0/0 and
+    /// cells of size 1. A piece without occupied cells has its center at 0/0.
+    pub fn center_of_mass(&self) -> (f64, f64) {
+        let count = self.occupied_count();
+        if count == 0 {
+            return (0.0, 0.0);
+        }
+
+        let (sum_x, sum_y) = (0..self.field.len())
+            .filter(|&i| self.field[i].is_occupied())
+            .fold((0.0, 0.0), |(sum_x, sum_y), i| {
+                let x = (i % self.width) as f64 + 0.5;
+                let y = (i / self.width) as f64 + 0.5;
+                (sum_x + x, sum_y + y)
+            });
+        (sum_x / count as f64, sum_y / count as f64)
+    }
+
+    /// Occupied cells divided by the area of their bounding box, 1 for a solid rectangle and 0
+    /// for a piece without occupied cells.
+    pub fn solidity(&self) -> f64 {
+        let trimmed = self.trimmed();
+        let area = trimmed.width * trimmed.height;
+        if area == 0 {
+            return 0.0;
+        }
+        self.occupied_count() as f64 / area as f64
+    }
+
+    /// Number of distinct orientations, 1 for a square up to 8 for a piece without symmetries.
+    pub fn variant_count(&self) -> usize {
+        self.all_variants().len()
+    }
+
+    /// Whether both pieces have the same cells in the same orientation. Unlike `==`, this ignores
+    /// the id, the weight and the flags. Compare the `canonical` pieces to also ignore
+    /// the orientation.
+    pub fn same_shape(&self, other: &Piece) -> bool {
+        self.width == other.width && self.height == other.height && self.field == other.field
+    }
+
+    /// Classifies the piece by the rotations and flips that map it onto itself. The number of
+    /// variants is 8 divided by the order of the group.
+    pub fn symmetry_group(&self) -> SymmetryGroup {
+        let rotated = self.rotated_cw();
+        let half_turn = rotated.rotated_cw();
+        let transforms = [
+            rotated.clone(),
+            half_turn.clone(),
+            half_turn.rotated_cw(),
+            self.flipped_horizontally(),
+            self.flipped_vertically(),
+            self.transposed(),
+            rotated.flipped_horizontally(),
+        ];
+        let symmetries = 1 + transforms.iter().filter(|&t| t == self).count();
+
+        let rotation = rotated == *self;
+        let half_rotation = half_turn == *self;
+        match symmetries {
+            8 => SymmetryGroup::D4,
+            4 if rotation => SymmetryGroup::C4,
+            4 => SymmetryGroup::D2,
+            2 if half_rotation => SymmetryGroup::C2,
+            2 => SymmetryGroup::D1,
+            _ => SymmetryGroup::C1,
+        }
+    }
+
+    /// The same orientation for all congruent pieces, with the id, weight and flags reset.
+    pub fn canonical(&self) -> Piece {
+        self.all_variants()
+            .into_iter()
+            .map(|mut variant| {
+                variant.id = 0;
+                variant.weight = 0;
+                variant.decorative = false;
+                variant.must_touch_border = false;
+                variant
+            })
+            .min_by_key(Piece::shape_key)
+            .unwrap()
+    }
+
+    /// Whether the occupied cells form a single region under `connectivity`.
+    pub fn is_connected(&self, connectivity: Connectivity) -> bool {
+        let occupied: Vec<usize> = (0..self.field.len())
+            .filter(|&i| self.field[i].is_occupied())
+            .collect();
+        let first = match occupied.first() {
+            Some(&first) => first,
+            None => return true,
+        };
+
+        let mut seen = vec![false; self.field.len()];
+        seen[first] = true;
+        let mut stack = vec![first];
+        let mut reached = 1;
+        while let Some(i) = stack.pop() {
+            for (x, y) in
+                connectivity.neighbors(i % self.width, i / self.width, self.width, self.height)
+            {
+                let n = x + y * self.width;
+                if !seen[n] && self.field[n].is_occupied() {
+                    seen[n] = true;
+                    reached += 1;
+                    stack.push(n);
+                }
+            }
+        }
+
+        reached == occupied.len()
+    }
+
+    /// All differently shaped 4-connected subsets of `size` occupied cells, as canonical pieces
+    /// ordered by their shape.
+    pub fn connected_subshapes(&self, size: usize) -> Vec<Piece> {
+        let occupied: HashSet<(usize, usize)> = (0..self.field.len())
+            .filter(|&i| self.field[i].is_occupied())
+            .map(|i| (i % self.width, i / self.width))
+            .collect();
+        if size == 0 || size > occupied.len() {
+            return vec![];
+        }
+
+        // grow the subsets one neighboring cell at a time, every subset with its cells sorted
+        let mut subsets: HashSet<Vec<(usize, usize)>> =
+            occupied.iter().map(|&cell| vec![cell]).collect();
+        for _ in 1..size {
+            let mut grown = HashSet::new();
+            for subset in subsets.iter() {
+                for &(x, y) in subset.iter() {
+                    let neighbors = [
+                        (x.wrapping_sub(1), y),
+                        (x + 1, y),
+                        (x, y.wrapping_sub(1)),
+                        (x, y + 1),
+                    ];
+                    for neighbor in neighbors.iter() {
+                        if occupied.contains(neighbor) && !subset.contains(neighbor) {
+                            let mut next = subset.clone();
+                            next.push(*neighbor);
+                            next.sort_unstable();
+                            grown.insert(next);
+                        }
+                    }
+                }
+            }
+            subsets = grown;
+        }
+
+        let shapes: HashSet<Piece> = subsets
+            .iter()
+            .map(|subset| Piece::from_coords(subset).canonical())
+            .collect();
+        let mut shapes: Vec<Piece> = shapes.into_iter().collect();
+        shapes.sort_by_cached_key(Piece::shape_key);
+        shapes
+    }
+
+    /// Orders pieces by their dimensions and then their occupancy, anchors last.
+    fn shape_key(&self) -> (usize, usize, Vec<u8>) {
+        let occupied = self
+            .field
+            .iter()
+            .map(|state| match *state {
+                PieceState::Free => 0,
+                PieceState::Occupied => 1,
+                PieceState::Anchor => 2,
+            })
+            .collect();
+        (self.width, self.height, occupied)
+    }
+
+    pub fn flipped_horizontally(&self) -> Piece {
+        let mut t = self.clone();
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let src_y = self.height - y - 1;
+                t.field[x + y * self.width] = self.field[x + src_y * self.width].clone();
+            }
+        }
+
+        t
+    }
+
+    pub fn flipped_vertically(&self) -> Piece {
+        let mut t = self.clone();
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let src_x = self.width - x - 1;
+                t.field[x + y * self.width] = self.field[src_x + y * self.width].clone();
+            }
+        }
+
+        t
+    }
+
+    /// Cropped to the bounding box of the occupied cells, keeping any anchors.
+    pub fn trimmed(&self) -> Piece {
+        let occupied: Vec<(usize, usize)> = (0..self.field.len())
+            .filter(|&i| self.field[i].is_occupied())
+            .map(|i| (i % self.width, i / self.width))
+            .collect();
+        let min_x = occupied.iter().map(|c| c.0).min().unwrap_or(0);
+        let min_y = occupied.iter().map(|c| c.1).min().unwrap_or(0);
+
+        let mut result = Piece::from_coords(&occupied);
+        result.id = self.id;
+        result.weight = self.weight;
+        result.decorative = self.decorative;
+        result.must_touch_border = self.must_touch_border;
+        for (x, y) in self.anchors() {
+            result.field[(x - min_x) + (y - min_y) * result.width] = PieceState::Anchor;
+        }
+        result
+    }
+
+    /// Rotated by 90 degrees clockwise.
+    pub fn rotated_cw(&self) -> Piece {
+        self.transposed().flipped_vertically()
+    }
+
+    /// Positions of the anchor cells.
+    pub fn anchors(&self) -> Vec<(usize, usize)> {
+        self.field
+            .iter()
+            .enumerate()
+            .filter(|&(_, state)| *state == PieceState::Anchor)
+            .map(|(i, _)| (i % self.width, i / self.width))
+            .collect()
+    }
+
+    pub fn transposed(&self) -> Piece {
+        let mut t = Piece {
+            id: self.id,
+            weight: self.weight,
+            decorative: self.decorative,
+            must_touch_border: self.must_touch_border,
+            width: self.height,
+            height: self.width,
+            field: vec![PieceState::Free; self.width * self.height],
+        };
+
+        for x in 0..t.width {
+            for y in 0..t.height {
+                t.field[x + y * t.width] = self.field[y + x * self.width].clone();
+            }
+        }
+
+        t
+    }
+
+    /// Builds a piece from its occupied cells, shifted so that its bounding box starts at 0/0.
+    pub fn from_coords(coords: &[(usize, usize)]) -> Piece {
+        let min_x = coords.iter().map(|c| c.0).min().unwrap_or(0);
+        let min_y = coords.iter().map(|c| c.1).min().unwrap_or(0);
+        let width = coords.iter().map(|c| c.0 - min_x + 1).max().unwrap_or(0);
+        let height = coords.iter().map(|c| c.1 - min_y + 1).max().unwrap_or(0);
+
+        let mut result = Piece {
+            id: 0,
+            weight: 0,
+            decorative: false,
+            must_touch_border: false,
+            width,
+            height,
+            field: vec![PieceState::Free; width * height],
+        };
+
+        for &(x, y) in coords {
+            result.field[(x - min_x) + (y - min_y) * width] = PieceState::Occupied;
+        }
+
+        result
+    }
+
+    /// Like `from_str`, but ignores trailing whitespace, which would otherwise widen the whole
+    /// piece. The downside is that a piece can't end with an entirely free column anymore.
+    pub fn from_str_trimmed(s: &str) -> Result<Piece, ParseError> {
+        let lines: Vec<&str> = s.split_terminator('\n').map(str::trim_end).collect();
+        lines.join("\n").parse()
+    }
+
+    /// Parses a piece that uses `filled` and `empty` instead of `X` and space. Anchors only exist
+    /// in the `X` format, so `O` is rejected like any other character.
+    pub fn from_str_with(s: &str, filled: char, empty: char) -> Result<Piece, ParseError> {
+        let lines: Vec<&str> = s.split_terminator('\n').collect();
+        Piece::from_rows_with(&lines, filled, empty, None)
+    }
+
+    /// Like `from_str`, but with every row as a separate string.
+    pub fn from_rows(rows: &[&str]) -> Result<Piece, ParseError> {
+        Piece::from_rows_with(rows, 'X', ' ', Some('O'))
+    }
+
+    fn from_rows_with(
+        lines: &[&str],
+        filled: char,
+        empty: char,
+        anchor: Option<char>,
+    ) -> Result<Piece, ParseError> {
+        let width = lines.iter().fold(0, |a, b| a.max(b.chars().count()));
+
+        if width == 0 {
+            return Err(ParseError::Empty);
+        }
+
+        let mut result = Piece {
+            id: 0,
+            weight: 0,
+            decorative: false,
+            must_touch_border: false,
+            width,
+            height: lines.len(),
+            field: vec![PieceState::Free; width * lines.len()],
+        };
+
+        for line in lines.iter().enumerate() {
+            for element in line.1.chars().enumerate() {
+                result.field[line.0 * width + element.0] = match element.1 {
+                    c if c == filled => PieceState::Occupied,
+                    c if c == empty => PieceState::Free,
+                    c if Some(c) == anchor => PieceState::Anchor,
+                    c if !c.is_ascii() => return Err(ParseError::NonAscii(c)),
+                    c => return Err(ParseError::UnexpectedCharacter(c)),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Sorts `pieces` by their canonical shape and previous id and renumbers them from 0. Fails
+/// with `ParseError::TooManyPieces`, leaving `pieces` unchanged, if there are more pieces than
+/// ids.
+pub fn normalize_pieces(pieces: &mut [Piece]) -> Result<(), ParseError> {
+    if pieces.len() > MAX_PIECES {
+        return Err(ParseError::TooManyPieces);
+    }
+    pieces.sort_by_cached_key(|piece| (piece.canonical().shape_key(), piece.id));
+    for (id, piece) in pieces.iter_mut().enumerate() {
+        piece.id = u8::try_from(id).map_err(|_| ParseError::TooManyPieces)?;
+    }
+    Ok(())
+}
+
+/// Whether both sets contain the same shapes, regardless of their order and orientation.
+pub fn piece_sets_equal(a: &[Piece], b: &[Piece]) -> bool {
+    let shapes = |pieces: &[Piece]| {
+        let mut shapes: Vec<_> = pieces
+            .iter()
+            .map(|piece| piece.canonical().shape_key())
+            .collect();
+        shapes.sort();
+        shapes
+    };
+
+    shapes(a) == shapes(b)
+}
+
+/// Parses the pieces of a pieces file, separated by empty lines, with the ids assigned in order.
+/// A piece can start with an `x<count>` line to add `count` copies of it.
+pub fn parse_pieces(content: &str) -> Result<Vec<Piece>, ParseError> {
+    Ok(parse_piece_blocks(content)?.into_iter().flatten().collect())
+}
+
+/// `parse_pieces`, but grouped by the pieces of the file, i.e. every group holds the copies of
+/// an `x<count>` piece or a single piece without a count.
+pub fn parse_piece_blocks(content: &str) -> Result<Vec<Vec<Piece>>, ParseError> {
+    let mut blocks = vec![];
+    let mut total = 0;
+    let mut current = vec![];
+    let mut count = None;
+    let mut finish = |current: &mut Vec<&str>, count: &mut Option<usize>| {
+        if current.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let piece = Piece::from_rows(current)?;
+        let count = count.take().unwrap_or(1);
+        if count > MAX_PIECES - total {
+            return Err(ParseError::TooManyPieces);
+        }
+        let block: Vec<Piece> = (total..total + count)
+            .map(|id| {
+                let mut copy = piece.clone();
+                copy.id = id as u8;
+                copy
+            })
+            .collect();
+        total += count;
+        blocks.push(block);
+        current.clear();
+        Ok(())
+    };
+
+    for line in content.lines() {
+        let prefix = line
+            .strip_prefix('x')
+            .filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+        match prefix {
+            Some(n) if current.is_empty() && count.is_none() => {
+                // stops at MAX_PIECES, so no count of any length overflows
+                let mut value: usize = 0;
+                for digit in n.bytes() {
+                    value = value
+                        .checked_mul(10)
+                        .and_then(|value| value.checked_add(usize::from(digit - b'0')))
+                        .filter(|&value| value <= MAX_PIECES)
+                        .ok_or(ParseError::TooManyPieces)?;
+                }
+                count = Some(value)
+            }
+            // an empty line ends the current piece
+            _ if line.is_empty() => finish(&mut current, &mut count)?,
+            _ => current.push(line),
+        }
+    }
+
+    if !current.is_empty() || count.is_some() {
+        finish(&mut current, &mut count)?;
+    }
+
+    Ok(blocks)
+}
+
+/// Pairs of ids of pieces with the same shape, e.g. a piece that was also listed rotated.
+pub fn congruent_pieces(pieces: &[Piece]) -> Vec<(u8, u8)> {
+    let canonicals: Vec<Piece> = pieces.iter().map(Piece::canonical).collect();
+    let mut pairs = vec![];
+    for (i, a) in canonicals.iter().enumerate() {
+        for (j, b) in canonicals.iter().enumerate().skip(i + 1) {
+            if a == b {
+                pairs.push((pieces[i].id, pieces[j].id));
+            }
+        }
+    }
+    pairs
+}
+
+/// Reads a field or pieces file, decompressing it first if its name ends in `.gz`.
+pub fn read_puzzle_file<P: AsRef<Path>>(path: P) -> Result<String, ParseError> {
+    let path = path.as_ref();
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        #[cfg(feature = "gzip")]
+        {
+            let data = crate::gzip::decompress(&fs::read(path)?)?;
+            return String::from_utf8(data).map_err(|_| ParseError::InvalidGzip);
+        }
+        #[cfg(not(feature = "gzip"))]
+        return Err(ParseError::InvalidGzip);
+    }
+
+    Ok(fs::read_to_string(path)?)
+}
+
+/// Reads every file in `path` as one piece, with the ids assigned in the order of the file names.
+pub fn load_pieces_from_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Piece>, ParseError> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    if paths.len() > MAX_PIECES {
+        return Err(ParseError::TooManyPieces);
+    }
+
+    paths
+        .iter()
+        .enumerate()
+        .map(|(id, path)| {
+            let mut piece: Piece = fs::read_to_string(path)?.trim_end_matches('\n').parse()?;
+            piece.id = id as u8;
+            Ok(piece)
+        })
+        .collect()
+}
+
+impl FromStr for Piece {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.split_terminator('\n').collect();
+        Piece::from_rows_with(&lines, 'X', ' ', Some('O'))
+    }
+}
+
+/// `{:#}` prepends a `Piece <id>` header line.
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            writeln!(f, "Piece {}", piece_char(self.id))?;
+        }
+        for e in self.field.iter().enumerate() {
+            if e.0 % self.width == 0 && e.0 != 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}",
+                match *e.1 {
+                    PieceState::Free => " ",
+                    PieceState::Occupied => "X",
+                    PieceState::Anchor => "O",
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(s: &str, id: u8) -> Piece {
+        let mut piece: Piece = s.parse().unwrap();
+        piece.id = id;
+        piece
+    }
+
+    #[test]
+    fn from_str_with_uses_the_given_characters() {
+        let custom = Piece::from_str_with("##\n.#", '#', '.').unwrap();
+        assert!(custom == piece("XX\n X", 0));
+        assert!(matches!(
+            Piece::from_str_with("#X", '#', '.'),
+            Err(ParseError::UnexpectedCharacter('X'))
+        ));
+    }
+
+    #[test]
+    fn alternate_display_names_the_piece() {
+        assert_eq!(format!("{:#}", piece("XX\n X", 2)), "Piece C\nXX\n X");
+        assert_eq!(format!("{}", piece("XX\n X", 2)), "XX\n X");
+    }
+
+    #[test]
+    fn from_coords_shifts_the_cells_to_the_origin() {
+        let piece_l = Piece::from_coords(&[(3, 5), (3, 6), (3, 7), (4, 7)]);
+        assert!(piece_l == piece("X\nX\nXX", 0));
+    }
+
+    #[test]
+    fn variant_count_depends_on_the_symmetry() {
+        assert_eq!(piece("XX\nXX", 0).variant_count(), 1);
+        assert_eq!(piece("XXX", 0).variant_count(), 2);
+        assert_eq!(piece("X\nX\nXX", 0).variant_count(), 8);
+    }
+
+    #[test]
+    fn normalize_pieces_does_not_depend_on_the_input_order() {
+        let mut pieces = vec![
+            piece("XXX", 7),
+            piece("X", 7),
+            piece("XX\nX", 30),
+            piece("X\nX\nX", 2),
+        ];
+        normalize_pieces(&mut pieces).unwrap();
+        assert_eq!(
+            pieces.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+
+        let mut shuffled = vec![
+            piece("X\nX\nX", 2),
+            piece("XX\nX", 30),
+            piece("X", 7),
+            piece("XXX", 7),
+        ];
+        normalize_pieces(&mut shuffled).unwrap();
+        assert!(pieces
+            .iter()
+            .zip(shuffled.iter())
+            .all(|(a, b)| a.canonical() == b.canonical()));
+    }
+
+    #[test]
+    fn pieces_without_cells_are_empty() {
+        assert!(matches!("\n\n".parse::<Piece>(), Err(ParseError::Empty)));
+        assert!(matches!("".parse::<Piece>(), Err(ParseError::Empty)));
+    }
+
+    #[test]
+    fn from_str_trimmed_ignores_trailing_whitespace() {
+        let trimmed = Piece::from_str_trimmed("XX  \n X\r\n").unwrap();
+        assert_eq!(trimmed.width, 2);
+        assert!(trimmed == piece("XX\n X", 0));
+        assert_eq!(piece("XX  \n X", 0).width, 4);
+        assert!(matches!(
+            Piece::from_str_trimmed("  \n "),
+            Err(ParseError::Empty)
+        ));
+    }
+
+    #[test]
+    fn load_pieces_from_dir_reads_the_files_in_name_order() {
+        let dir = std::env::temp_dir().join(format!(
+            "just_in_time_game_load_pieces_from_dir_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.txt"), "X\nX\n").unwrap();
+        fs::write(dir.join("a.txt"), "XX\n X\n\n").unwrap();
+
+        let pieces = load_pieces_from_dir(&dir).unwrap();
+        assert_eq!(pieces.len(), 2);
+        assert!(pieces[0] == piece("XX\n X", 0));
+        assert!(pieces[1] == piece("X\nX", 1));
+
+        fs::write(dir.join("c.txt"), "Y").unwrap();
+        assert!(matches!(
+            load_pieces_from_dir(&dir),
+            Err(ParseError::UnexpectedCharacter('Y'))
+        ));
+        assert!(matches!(
+            load_pieces_from_dir(dir.join("missing")),
+            Err(ParseError::Io(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn piece_sets_equal_ignores_order_and_orientation() {
+        let set = [piece("XX\nX", 0), piece("XXX", 1)];
+        assert!(piece_sets_equal(
+            &set,
+            &[piece("X\nX\nX", 5), piece("X\nXX", 2)]
+        ));
+        assert!(!piece_sets_equal(
+            &set,
+            &[piece("X\nX\nX", 5), piece("XX", 2)]
+        ));
+        assert!(!piece_sets_equal(
+            &set[..1],
+            &[piece("XX\nX", 0), piece("XX\nX", 0)]
+        ));
+    }
+
+    #[test]
+    fn non_ascii_characters_are_reported_as_such() {
+        assert!(matches!(
+            "Xé".parse::<Piece>(),
+            Err(ParseError::NonAscii('é'))
+        ));
+        assert!(matches!(
+            "X#".parse::<Piece>(),
+            Err(ParseError::UnexpectedCharacter('#'))
+        ));
+        let blocks = Piece::from_str_with("██\n█·", '█', '·').unwrap();
+        assert_eq!((blocks.width, blocks.occupied_count()), (2, 3));
+    }
+
+    #[test]
+    fn from_rows_pads_short_rows() {
+        let l = Piece::from_rows(&["X", "X", "XX"]).unwrap();
+        assert_eq!(l.to_string(), "X \nX \nXX");
+        assert_eq!(l.occupied_count(), 4);
+        assert!(matches!(
+            Piece::from_rows(&["X", "o"]),
+            Err(ParseError::UnexpectedCharacter('o'))
+        ));
+        assert!(matches!(Piece::from_rows(&[]), Err(ParseError::Empty)));
+    }
+
+    #[test]
+    fn anchors_are_occupied_and_follow_the_rotation() {
+        let anchored = piece("XO\nX \nX ", 0);
+        assert_eq!(anchored.anchors(), vec![(1, 0)]);
+        assert_eq!(anchored.occupied_count(), 4);
+
+        let rotated = anchored.rotated_cw();
+        assert_eq!(rotated.to_string(), "XXX\n  O");
+        assert_eq!(rotated.anchors(), vec![(2, 1)]);
+        assert!(rotated.rotated_cw().rotated_cw().rotated_cw() == anchored);
+        assert_eq!(anchored.variant_count(), 8);
+        assert!(anchored
+            .all_variants()
+            .iter()
+            .all(|variant| variant.anchors().len() == 1));
+    }
+
+    #[test]
+    fn anchors_only_exist_in_the_x_format() {
+        assert_eq!(Piece::from_rows(&["XO"]).unwrap().anchors(), vec![(1, 0)]);
+        assert!(matches!(
+            Piece::from_str_with("#O", '#', '.'),
+            Err(ParseError::UnexpectedCharacter('O'))
+        ));
+        assert_eq!(
+            Piece::from_str_with("#O", '#', 'O')
+                .unwrap()
+                .occupied_count(),
+            1
+        );
+    }
+
+    #[test]
+    fn connected_subshapes_lists_the_distinct_shapes() {
+        let l = piece("X\nX\nXX", 0);
+        let triominoes = l.connected_subshapes(3);
+        assert_eq!(triominoes.len(), 2);
+        assert!(triominoes.contains(&piece("XXX", 0).canonical()));
+        assert!(triominoes.contains(&piece("XX\nX", 0).canonical()));
+        assert!(l.connected_subshapes(4) == vec![l.canonical()]);
+        assert_eq!(l.connected_subshapes(1).len(), 1);
+        assert!(l.connected_subshapes(5).is_empty());
+        assert!(l.connected_subshapes(0).is_empty());
+        // every tetromino but the I, which is longer than 3
+        assert_eq!(piece("XXX\nXXX\nXXX", 0).connected_subshapes(4).len(), 4);
+    }
+
+    #[test]
+    fn center_of_mass_averages_the_cell_centers() {
+        assert_eq!(piece("XX\nXX", 0).center_of_mass(), (1.0, 1.0));
+        assert_eq!(piece("XXX\nX X\nXXX", 0).center_of_mass(), (1.5, 1.5));
+        let (x, y) = piece("X\nXX", 0).center_of_mass();
+        assert!((x - 2.5 / 3.0).abs() < 1e-12);
+        assert!((y - 3.5 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn trimmed_removes_free_rows_and_columns() {
+        let padded: Piece = "XX \nX  \n   ".parse().unwrap();
+        assert_eq!((padded.width, padded.height), (3, 3));
+        assert_eq!(padded.trimmed().to_string(), "XX\nX ");
+        assert_eq!(piece("  \n O\n X", 0).trimmed().to_string(), "O\nX");
+    }
+
+    #[test]
+    fn symmetry_group_matches_the_variant_count() {
+        let cases = [
+            ("XX\nXX", SymmetryGroup::D4),
+            ("X", SymmetryGroup::D4),
+            (" X\nXXX\n X", SymmetryGroup::D4),
+            (" X  \n XXX\nXXX \n  X ", SymmetryGroup::C4),
+            ("XXX", SymmetryGroup::D2),
+            ("XX\n XX", SymmetryGroup::C2),
+            ("X\nXXX\n  X", SymmetryGroup::C2),
+            ("X\nXX", SymmetryGroup::D1),
+            ("XXX\n X", SymmetryGroup::D1),
+            ("XX\nX\nXX", SymmetryGroup::D1),
+            ("X\nX\nXX", SymmetryGroup::C1),
+        ];
+        for &(shape, group) in cases.iter() {
+            let tested = piece(shape, 0);
+            assert_eq!(tested.symmetry_group(), group, "{}", shape);
+            assert_eq!(tested.variant_count() * group.order(), 8, "{}", shape);
+        }
+    }
+
+    #[test]
+    fn same_shape_ignores_the_id_but_not_the_orientation() {
+        let a = piece("XX\nX", 0);
+        let b = piece("XX\nX", 1);
+        assert!(a.same_shape(&b));
+        assert!(a != b);
+        assert!(!a.same_shape(&a.rotated_cw()));
+        assert!(a.canonical().same_shape(&a.rotated_cw().canonical()));
+    }
+
+    #[test]
+    fn parse_pieces_splits_at_empty_lines() {
+        let pieces = parse_pieces("XX\nX\n\nXXX\n\nX\nXX\n\nXXX\n").unwrap();
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces
+            .iter()
+            .enumerate()
+            .all(|(i, piece)| piece.id == i as u8));
+        assert!(pieces[1].same_shape(&piece("XXX", 0)));
+        assert!(matches!(parse_pieces("X\n\n\nX"), Err(ParseError::Empty)));
+    }
+
+    #[test]
+    fn congruent_pieces_pairs_rotated_and_repeated_pieces() {
+        let pieces = parse_pieces("XX\nX\n\nXXX\n\nX\nXX\n\nXXX\n").unwrap();
+        assert_eq!(congruent_pieces(&pieces), vec![(0, 2), (1, 3)]);
+        assert!(congruent_pieces(&pieces[..2]).is_empty());
+    }
+
+    #[test]
+    fn is_connected_uses_the_connectivity() {
+        let diagonal = piece("X \n X", 0);
+        assert!(!diagonal.is_connected(Connectivity::Four));
+        assert!(diagonal.is_connected(Connectivity::Eight));
+        assert!(piece("XX\n X", 0).is_connected(Connectivity::default()));
+        assert!(!piece("X X", 0).is_connected(Connectivity::Eight));
+    }
+
+    #[test]
+    fn parse_pieces_rejects_more_than_256_pieces() {
+        let pieces = parse_pieces("x255\nX\n\nXX").unwrap();
+        assert_eq!(pieces.len(), 256);
+        assert_eq!(pieces[255].id, 255);
+        assert!(matches!(
+            parse_pieces("x256\nX\n\nXX"),
+            Err(ParseError::TooManyPieces)
+        ));
+        assert!(matches!(
+            parse_pieces("x18446744073709551615\nX"),
+            Err(ParseError::TooManyPieces)
+        ));
+        assert!(matches!(
+            parse_pieces("x99999999999999999999999999\nX"),
+            Err(ParseError::TooManyPieces)
+        ));
+    }
+
+    #[test]
+    fn parse_piece_blocks_groups_the_copies_of_a_piece() {
+        let blocks = parse_piece_blocks("x2\nXX\nX\n\nX\nXX\n\nx0\nX\n\nXXX").unwrap();
+        let ids: Vec<Vec<u8>> = blocks
+            .iter()
+            .map(|block| block.iter().map(|piece| piece.id).collect())
+            .collect();
+        assert_eq!(ids, vec![vec![0, 1], vec![2], vec![], vec![3]]);
+        assert!(blocks[0][1] == piece("XX\nX", 1));
+    }
+
+    #[test]
+    fn load_pieces_from_dir_rejects_more_than_256_files() {
+        let dir = std::env::temp_dir().join(format!(
+            "just_in_time_game_load_pieces_from_dir_limit_{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..257 {
+            fs::write(dir.join(format!("{:03}.txt", i)), "X").unwrap();
+        }
+        assert!(matches!(
+            load_pieces_from_dir(&dir),
+            Err(ParseError::TooManyPieces)
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn piece_char_covers_every_id() {
+        assert_eq!(piece_char(0), 'A');
+        assert_eq!(piece_char(25), 'Z');
+        assert_eq!(piece_char(255), '\u{140}');
+
+        assert_eq!(format!("{:#}", piece("X", 255)), "Piece \u{140}\nX");
+    }
+
+    #[test]
+    fn variants_keep_must_touch_border() {
+        let mut corner = piece("XX\nX", 0);
+        corner.must_touch_border = true;
+        assert!(corner.all_variants().iter().all(|v| v.must_touch_border));
+        assert!(corner.canonical() == piece("XX\nX", 0).canonical());
+    }
+
+    #[test]
+    fn solidity_is_the_share_of_the_bounding_box_that_is_occupied() {
+        assert_eq!(piece("XX\nXX\nXX", 0).solidity(), 1.0);
+        assert_eq!(piece(" X \nXXX\n X ", 0).solidity(), 5.0 / 9.0);
+        // free borders don't count towards the bounding box
+        assert_eq!(piece("  \n XX", 0).solidity(), 1.0);
+    }
+
+    #[test]
+    fn normalize_pieces_rejects_more_than_256_pieces() {
+        let mut pieces: Vec<Piece> = (0..257).map(|_| piece("X", 0)).collect();
+        assert!(matches!(
+            normalize_pieces(&mut pieces),
+            Err(ParseError::TooManyPieces)
+        ));
+
+        pieces.pop();
+        normalize_pieces(&mut pieces).unwrap();
+        assert_eq!(pieces[255].id, 255);
+    }
+}
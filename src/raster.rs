@@ -0,0 +1,213 @@
+use crate::field::{piece_hue, CatalogLayout};
+use crate::piece::Piece;
+
+/// 3x5 bitmap glyphs of the letters `A` to `Z`, one byte per row with the left pixel in bit 2.
+const GLYPHS: [[u8; 5]; 26] = [
+    [0b010, 0b101, 0b111, 0b101, 0b101],
+    [0b110, 0b101, 0b110, 0b101, 0b110],
+    [0b011, 0b100, 0b100, 0b100, 0b011],
+    [0b110, 0b101, 0b101, 0b101, 0b110],
+    [0b111, 0b100, 0b110, 0b100, 0b111],
+    [0b111, 0b100, 0b110, 0b100, 0b100],
+    [0b011, 0b100, 0b101, 0b101, 0b011],
+    [0b101, 0b101, 0b111, 0b101, 0b101],
+    [0b111, 0b010, 0b010, 0b010, 0b111],
+    [0b001, 0b001, 0b001, 0b101, 0b010],
+    [0b101, 0b101, 0b110, 0b101, 0b101],
+    [0b100, 0b100, 0b100, 0b100, 0b111],
+    [0b101, 0b111, 0b111, 0b101, 0b101],
+    [0b110, 0b101, 0b101, 0b101, 0b101],
+    [0b010, 0b101, 0b101, 0b101, 0b010],
+    [0b110, 0b101, 0b110, 0b100, 0b100],
+    [0b010, 0b101, 0b101, 0b110, 0b011],
+    [0b110, 0b101, 0b110, 0b101, 0b101],
+    [0b011, 0b100, 0b010, 0b001, 0b110],
+    [0b111, 0b010, 0b010, 0b010, 0b010],
+    [0b101, 0b101, 0b101, 0b101, 0b111],
+    [0b101, 0b101, 0b101, 0b101, 0b010],
+    [0b101, 0b101, 0b111, 0b111, 0b101],
+    [0b101, 0b101, 0b010, 0b101, 0b101],
+    [0b101, 0b101, 0b010, 0b010, 0b010],
+    [0b111, 0b001, 0b010, 0b100, 0b111],
+];
+
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+/// 8 bit RGBA image with the pixels stored row by row, the same layout as the `RgbaImage` of
+/// the `image` crate, so `image::RgbaImage::from_raw(width, height, image.into_raw())` converts
+/// it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// A white image.
+    pub fn new(width: u32, height: u32) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            data: WHITE.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (x as usize + y as usize * self.width as usize) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let i = (x as usize + y as usize * self.width as usize) * 4;
+        self.data[i..i + 4].copy_from_slice(&pixel);
+    }
+
+    /// The RGBA bytes of all pixels.
+    pub fn into_raw(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn fill_rect(&mut self, x: u32, y: u32, w: u32, h: u32, pixel: [u8; 4]) {
+        for pixel_y in y..(y + h).min(self.height) {
+            for pixel_x in x..(x + w).min(self.width) {
+                self.put_pixel(pixel_x, pixel_y, pixel);
+            }
+        }
+    }
+}
+
+/// The `hsl(hue, 70%, 60%)` color of `render_svg` as RGBA.
+fn piece_pixel(id: u8) -> [u8; 4] {
+    let (saturation, lightness) = (0.7, 0.6);
+    let chroma = (1.0 - (2.0 * lightness - 1.0f64).abs()) * saturation;
+    let hue = f64::from(piece_hue(id)) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |c: f64| ((c + m) * 255.0).round() as u8;
+    [channel(r), channel(g), channel(b), 255]
+}
+
+/// Contact sheet of the canonical orientations of `pieces` as an image, with the same layout as
+/// `render_catalog_svg`. The label is the letter of the piece id, drawn as a bitmap glyph that
+/// fills the label row, pieces with ids beyond `Z` stay unlabeled.
+pub fn render_catalog(pieces: &[Piece], cell: u32, cols: usize) -> RgbaImage {
+    let layout = CatalogLayout::new(pieces, cols);
+    let slot_width = layout.slot_width * cell;
+    let slot_height = layout.slot_height * cell;
+    let mut image = RgbaImage::new(
+        layout.cols as u32 * slot_width,
+        layout.rows as u32 * slot_height,
+    );
+
+    // glyph pixels are scaled to fit into a cell, with a margin of one glyph pixel
+    let scale = (cell / 7).max(1);
+    for (n, shape) in layout.shapes.iter().enumerate() {
+        let left = (n % layout.cols) as u32 * slot_width + cell / 2;
+        let top = (n / layout.cols) as u32 * slot_height;
+
+        if let Some(glyph) = GLYPHS.get(usize::from(shape.id)) {
+            for (row, bits) in glyph.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        let x = left + (column + 1) * scale;
+                        let y = top + (row as u32 + 1) * scale;
+                        image.fill_rect(x, y, scale, scale, BLACK);
+                    }
+                }
+            }
+        }
+
+        for (i, state) in shape.field.iter().enumerate() {
+            if state.is_occupied() {
+                let x = left + (i % shape.width) as u32 * cell;
+                let y = top + (i / shape.width) as u32 * cell + cell;
+                image.fill_rect(x, y, cell, cell, BLACK);
+                if cell > 2 {
+                    image.fill_rect(x + 1, y + 1, cell - 2, cell - 2, piece_pixel(shape.id));
+                }
+            }
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(s: &str, id: u8) -> Piece {
+        let mut piece: Piece = s.parse().unwrap();
+        piece.id = id;
+        piece
+    }
+
+    #[test]
+    fn piece_pixel_matches_the_svg_color() {
+        // hsl(0, 70%, 60%) and hsl(137, 70%, 60%)
+        assert_eq!(piece_pixel(0), [224, 82, 82, 255]);
+        assert_eq!(piece_pixel(1), [82, 224, 122, 255]);
+    }
+
+    #[test]
+    fn render_catalog_lays_the_pieces_out_in_a_grid() {
+        let pieces = [
+            piece("XX\nXX", 0),
+            piece("XXXX", 1),
+            piece("X", 2),
+            piece("XX\nX", 3),
+            piece("X\nXX\n X", 4),
+        ];
+        let layout = CatalogLayout::new(&pieces, 2);
+        assert_eq!((layout.cols, layout.rows), (2, 3));
+
+        let image = render_catalog(&pieces, 10, 2);
+        let expected = (2 * layout.slot_width * 10, 3 * layout.slot_height * 10);
+        assert_eq!(image.dimensions(), expected);
+        assert_eq!(
+            image.clone().into_raw().len(),
+            (expected.0 * expected.1 * 4) as usize
+        );
+
+        // the top left cell of the first piece, its border and the background next to it
+        assert_eq!(image.get_pixel(10, 15), piece_pixel(0));
+        assert_eq!(image.get_pixel(5, 15), BLACK);
+        assert_eq!(image.get_pixel(2, 15), WHITE);
+        // the left column of the `A` glyph
+        assert_eq!(image.get_pixel(6, 3), BLACK);
+    }
+
+    #[test]
+    fn render_catalog_without_pieces_is_empty() {
+        let image = render_catalog(&[], 10, 3);
+        assert_eq!(image.height(), 0);
+        assert!(image.into_raw().is_empty());
+    }
+}
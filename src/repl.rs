@@ -0,0 +1,252 @@
+use std::io::{self, BufRead, Write};
+
+use just_in_time_game::{piece_char, Field, Piece, Solution};
+
+const HELP: &str = "commands:
+  place <piece> <x> <y> [variant]  place a piece with its top left corner at x/y
+  remove <piece>                   take a placed piece off the board again
+  undo                             revert the last place or remove
+  show                             print the board
+  variants <piece>                 print the variants of a piece
+  score                            print the leftover score
+  solve                            solve the board with the pieces that aren't placed yet
+  check <on|off>                   warn after place if the remaining pieces don't fit anymore,
+                                   this searches the board after every place and is off by default
+  quit";
+
+/// The board of an interactive session together with its history for `undo`.
+struct Session {
+    start: Field,
+    pieces: Vec<Piece>,
+    history: Vec<Field>,
+    /// whether `place` checks that the remaining pieces can still be placed
+    check: bool,
+}
+
+impl Session {
+    fn current(&self) -> &Field {
+        self.history.last().unwrap_or(&self.start)
+    }
+
+    fn piece(&self, name: Option<&str>) -> Result<&Piece, String> {
+        let name = name.ok_or("missing piece")?;
+        self.pieces
+            .iter()
+            .find(|piece| name.eq_ignore_ascii_case(&piece_char(piece.id).to_string()))
+            .ok_or_else(|| format!("unknown piece {}", name))
+    }
+
+    fn remaining_pieces(&self) -> Vec<Piece> {
+        self.pieces
+            .iter()
+            .filter(|piece| !self.is_placed(piece))
+            .cloned()
+            .collect()
+    }
+
+    fn is_placed(&self, piece: &Piece) -> bool {
+        let board = self.current();
+        (0..board.height).any(|y| (0..board.width).any(|x| board.piece_at(x, y) == Some(piece.id)))
+    }
+
+    /// Executes a single command, returns the text to print.
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let number = |word: Option<&str>, what: &str| -> Result<usize, String> {
+            word.ok_or(format!("missing {}", what))?
+                .parse()
+                .map_err(|_| format!("invalid {}", what))
+        };
+
+        match words.next() {
+            Some("place") => {
+                let piece = self.piece(words.next())?;
+                let x = number(words.next(), "x")?;
+                let y = number(words.next(), "y")?;
+                let variant = match words.next() {
+                    Some(word) => number(Some(word), "variant")?,
+                    None => 0,
+                };
+
+                if self.is_placed(piece) {
+                    return Err("the piece is already placed".to_string());
+                }
+                let variants = piece.all_variants();
+                let variant = variants
+                    .get(variant)
+                    .ok_or(format!("the piece only has {} variants", variants.len()))?;
+
+                let mut board = self.current().clone();
+                if !board.try_place(variant, x, y) {
+                    return Err("the piece doesn't fit there".to_string());
+                }
+                self.history.push(board);
+                if !self.check
+                    || Solution::is_still_solvable(self.current(), &self.remaining_pieces())
+                {
+                    Ok(self.current().to_string())
+                } else {
+                    Ok(format!(
+                        "{}\nwarning: the remaining pieces don't fit anymore",
+                        self.current()
+                    ))
+                }
+            }
+            Some("remove") => {
+                let id = self.piece(words.next())?.id;
+                let mut board = self.current().clone();
+                if !board.remove_placement(id, &self.start) {
+                    return Err("the piece isn't placed".to_string());
+                }
+                self.history.push(board);
+                Ok(self.current().to_string())
+            }
+            Some("undo") => match self.history.pop() {
+                Some(_) => Ok(self.current().to_string()),
+                None => Err("nothing to undo".to_string()),
+            },
+            Some("show") => Ok(self.current().to_string()),
+            Some("variants") => {
+                let piece = self.piece(words.next())?;
+                let variants: Vec<String> = piece
+                    .all_variants()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| format!("{}:\n{}", i, variant))
+                    .collect();
+                Ok(variants.join("\n\n"))
+            }
+            Some("score") => Ok(self.current().count().to_string()),
+            Some("solve") => {
+                let solution = Solution::new(self.current(), &self.remaining_pieces());
+                match solution.best_solutions().first() {
+                    Some(best) => Ok(format!(
+                        "{} solutions, best score {}\n{}",
+                        solution.solutions.len(),
+                        best.count(),
+                        best
+                    )),
+                    None => Err("no solution".to_string()),
+                }
+            }
+            Some("check") => {
+                self.check = match words.next() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err("expected check on or check off".to_string()),
+                };
+                Ok(String::new())
+            }
+            Some("help") => Ok(HELP.to_string()),
+            Some(command) => Err(format!("unknown command {}, try help", command)),
+            None => Ok(String::new()),
+        }
+    }
+}
+
+/// Reads commands from `input` until it ends or `quit` is entered.
+pub fn run<R: BufRead, W: Write>(
+    start: Field,
+    pieces: Vec<Piece>,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    let mut session = Session {
+        start,
+        pieces,
+        history: vec![],
+        check: false,
+    };
+
+    writeln!(output, "{}", session.current())?;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim() == "quit" {
+            break;
+        }
+
+        match session.execute(&line) {
+            Ok(text) if text.is_empty() => {}
+            Ok(text) => writeln!(output, "{}", text)?,
+            Err(message) => writeln!(output, "error: {}", message)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the commands of `script` on `start` with a domino `A` and a monomino `B`.
+    fn session(start: &str, script: &str) -> String {
+        let mut domino: Piece = "XX".parse().unwrap();
+        let mut monomino: Piece = "X".parse().unwrap();
+        domino.id = 0;
+        monomino.id = 1;
+
+        let mut output = vec![];
+        let pieces = vec![domino, monomino];
+        run(
+            start.parse().unwrap(),
+            pieces,
+            script.as_bytes(),
+            &mut output,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn executes_the_commands_until_quit() {
+        let script = "\
+place A 0 0
+place B 9 9
+place B 2 0
+undo
+score
+remove B
+bogus
+solve
+quit
+show
+";
+        let expected = "\
+12-
+---
+A2-
+A--
+error: the piece doesn't fit there
+A2B
+A--
+A2-
+A--
+2
+error: the piece isn't placed
+error: unknown command bogus, try help
+4 solutions, best score 2
+A2B
+A--
+";
+        assert_eq!(session("12-\n---", script), expected);
+    }
+
+    #[test]
+    fn check_warns_about_unsolvable_boards_only_when_enabled() {
+        let warning = "warning: the remaining pieces don't fit anymore";
+        assert!(!session("---", "place B 1 0\n").contains(warning));
+        assert!(session("---", "check on\nplace B 1 0\n").contains(warning));
+        assert!(!session("---", "check on\ncheck off\nplace B 1 0\n").contains(warning));
+        assert!(session("---", "check maybe\n").contains("error: expected check on or check off"));
+    }
+
+    #[test]
+    fn place_rejects_coordinates_off_the_board() {
+        let script = format!("place A {} 0\nplace A 0 {}\n", usize::MAX, usize::MAX);
+        assert_eq!(
+            session("--", &script),
+            "--\nerror: the piece doesn't fit there\nerror: the piece doesn't fit there\n"
+        );
+    }
+}
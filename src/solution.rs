@@ -0,0 +1,2617 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::error::ParseError;
+use crate::field::{Field, FieldState, LayeredField};
+use crate::piece::Piece;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SolveStats {
+    /// number of placements visited by the search
+    pub nodes: usize,
+    /// number of boards whose placements were enumerated
+    pub expanded: usize,
+}
+
+/// What `best_solutions` maximizes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Objective {
+    /// the score of the cells that are left free
+    #[default]
+    Leftover,
+    /// the score of the cells covered by pieces
+    Covered,
+    /// the leftover score plus the weights of the placed pieces
+    Weighted,
+    /// the number of placed pieces, ties are broken by the leftover score
+    MaxPieces,
+}
+
+/// Configuration of `Solution::solve_with`, the defaults match `Solution::new`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolveOptions {
+    objective: Objective,
+    max_solutions: Option<usize>,
+    stop_at_perfect: bool,
+    best_only: bool,
+    allow_overhang: bool,
+    anchor_score: Option<i16>,
+}
+
+impl SolveOptions {
+    /// The objective of the returned solution, used by `best_only`.
+    pub fn objective(mut self, objective: Objective) -> SolveOptions {
+        self.objective = objective;
+        self
+    }
+
+    /// Stops after recording `max` solutions.
+    pub fn max_solutions(mut self, max: usize) -> SolveOptions {
+        self.max_solutions = Some(max);
+        self
+    }
+
+    /// Stops at the first solution that covers the `max_possible_score`, like
+    /// `solve_until_perfect`. The result ranks by `Objective::Covered` then, the score the stop
+    /// is about, whatever `objective` is set to.
+    pub fn stop_at_perfect(mut self, stop: bool) -> SolveOptions {
+        self.stop_at_perfect = stop;
+        self
+    }
+
+    /// Only keeps the `best_solutions` of the recorded ones.
+    pub fn best_only(mut self, best_only: bool) -> SolveOptions {
+        self.best_only = best_only;
+        self
+    }
+
+    /// Lets the free cells of the pieces hang off the board, like `solve_allow_overhang`.
+    pub fn allow_overhang(mut self, allow: bool) -> SolveOptions {
+        self.allow_overhang = allow;
+        self
+    }
+
+    /// Anchor cells may only cover free cells with `score`, like `solve_with_anchor_score`.
+    pub fn anchor_score(mut self, score: i16) -> SolveOptions {
+        self.anchor_score = Some(score);
+        self
+    }
+}
+
+/// SplitMix64, good enough to pick samples reproducibly.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`, without the bias of a plain modulo.
+    fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let value = self.next();
+            if value < zone {
+                return (value % n) as usize;
+            }
+        }
+    }
+}
+
+/// Summary of how varied the solutions of a puzzle are, see `Solution::metrics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolutionMetrics {
+    /// number of recorded solutions
+    pub solutions: usize,
+    /// number of different boards among them
+    pub distinct_solutions: usize,
+    /// number of different sets of covered cells, regardless of which piece covers them
+    pub coverage_classes: usize,
+    /// lowest and highest leftover score, 0 without solutions
+    pub min_score: i32,
+    pub max_score: i32,
+    /// mean leftover score and its standard deviation
+    pub mean_score: f64,
+    pub score_deviation: f64,
+}
+
+/// Partial board of the best first search, ordered so that the `BinaryHeap` pops the lowest
+/// bound first and the oldest board among equal bounds.
+struct Node {
+    bound: i32,
+    seq: usize,
+    field: Field,
+    depth: usize,
+}
+
+impl Node {
+    fn key(&self) -> (Reverse<i32>, Reverse<usize>) {
+        (Reverse(self.bound), Reverse(self.seq))
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+pub struct Solution {
+    pub start: Field,
+    pub pieces: Vec<Vec<Piece>>,
+    pub solutions: Vec<Field>,
+    pub stats: SolveStats,
+    pub objective: Objective,
+}
+
+impl Solution {
+    /// Without any pieces the unchanged start field is the only solution.
+    pub fn new(start: &Field, pieces: &[Piece]) -> Solution {
+        Solution::solve_with(start, pieces, &SolveOptions::default())
+    }
+
+    /// Solves with all the `options` combined.
+    pub fn solve_with(start: &Field, pieces: &[Piece], options: &SolveOptions) -> Solution {
+        let pieces: Vec<Piece> = if options.allow_overhang {
+            pieces.iter().map(Piece::trimmed).collect()
+        } else {
+            pieces.to_vec()
+        };
+
+        let accept = |_: &Field, piece: &Piece, (x, y): (usize, usize)| match options.anchor_score {
+            Some(score) => piece.anchors().iter().all(|&(anchor_x, anchor_y)| {
+                start.field[x + anchor_x + (y + anchor_y) * start.width] == FieldState::Free(score)
+            }),
+            None => true,
+        };
+
+        // decorative pieces don't cover any score
+        let bound = start.max_possible_score(&Solution::scoring_pieces(&pieces));
+        let decorative: HashSet<u8> = pieces
+            .iter()
+            .filter(|p| p.decorative)
+            .map(|p| p.id)
+            .collect();
+        let total = start.count();
+        let mut found = 0;
+        let mut solution = Solution::collect(start, &pieces, &accept, |field| {
+            found += 1;
+            let covered = total - Solution::leftover_with(start, &decorative, field);
+            let perfect = options.stop_at_perfect && covered >= bound;
+            !perfect && options.max_solutions.is_none_or(|max| found < max)
+        });
+
+        if let Some(max) = options.max_solutions {
+            solution.solutions.truncate(max);
+        }
+        solution.objective = if options.stop_at_perfect {
+            Objective::Covered
+        } else {
+            options.objective
+        };
+        if options.best_only {
+            solution.solutions = solution.best_solutions();
+        }
+
+        solution
+    }
+
+    /// `new` for each of `fields` with the same `pieces`. The variants are computed once and the
+    /// fields are solved on all available cores, the results are in the order of `fields`.
+    pub fn solve_many(fields: &[Field], pieces: &[Piece]) -> Vec<Solution> {
+        let variants: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = fields.len().div_ceil(threads).max(1);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = fields
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let variants = &variants;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|field| {
+                                Solution::collect_variants(field, variants, &|_, _, _| true, |_| {
+                                    true
+                                })
+                            })
+                            .collect::<Vec<Solution>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    /// Like `new`, but the free cells of the pieces may hang off the board, see
+    /// `Field::place_iter_allow_overhang`.
+    pub fn solve_allow_overhang(start: &Field, pieces: &[Piece]) -> Solution {
+        Solution::solve_with(start, pieces, &SolveOptions::default().allow_overhang(true))
+    }
+
+    /// Like `new`, but the anchor cells of the pieces may only cover free cells with `score`.
+    pub fn solve_with_anchor_score(start: &Field, pieces: &[Piece], score: i16) -> Solution {
+        Solution::solve_with(start, pieces, &SolveOptions::default().anchor_score(score))
+    }
+
+    /// Solves while only allowing placements inside `x0..x1` and `y0..y1` of `region`. Cells
+    /// outside of it are left untouched.
+    pub fn solve_region(
+        start: &Field,
+        pieces: &[Piece],
+        region: (usize, usize, usize, usize),
+    ) -> Solution {
+        let (x0, y0, x1, y1) = region;
+        let inside = |i: usize| {
+            let (x, y) = (i % start.width, i / start.width);
+            x >= x0 && x < x1 && y >= y0 && y < y1
+        };
+
+        let mut masked = start.clone();
+        for (i, state) in masked.field.iter_mut().enumerate() {
+            if !inside(i) {
+                *state = FieldState::Blocked;
+            }
+        }
+
+        let mut solution = Solution::new(&masked, pieces);
+        for field in solution.solutions.iter_mut() {
+            for (i, state) in field.field.iter_mut().enumerate() {
+                if !inside(i) {
+                    *state = start.field[i].clone();
+                }
+            }
+        }
+        solution.start = start.clone();
+
+        solution
+    }
+
+    /// Like `new`, but only recurses into placements that `accept` returns true for. `accept`
+    /// gets the field after the placement, the placed variant and its position.
+    pub fn solve_with_filter<F>(start: &Field, pieces: &[Piece], accept: F) -> Solution
+    where
+        F: Fn(&Field, &Piece, (usize, usize)) -> bool,
+    {
+        Solution::collect(start, pieces, &accept, |_| true)
+    }
+
+    /// Like `new`, but stops as soon as `cancel` is set and returns the solutions found until then.
+    pub fn solve_cancellable(
+        start: &Field,
+        pieces: &[Piece],
+        cancel: Arc<AtomicBool>,
+    ) -> Vec<Field> {
+        let cancelled = || cancel.load(AtomicOrdering::Relaxed);
+        Solution::collect(start, pieces, &|_, _, _| !cancelled(), |_| !cancelled()).solutions
+    }
+
+    /// Like `new`, but the pieces may not cover the cells listed for their id in `forbidden`.
+    /// Cells outside of `start` are ignored.
+    pub fn solve_with_forbidden(
+        start: &Field,
+        pieces: &[Piece],
+        forbidden: &HashMap<u8, Vec<(usize, usize)>>,
+    ) -> Solution {
+        Solution::solve_with_filter(start, pieces, |placement, piece, _| {
+            forbidden
+                .get(&piece.id)
+                .into_iter()
+                .flatten()
+                .filter(|&&(x, y)| x < placement.width && y < placement.height)
+                .all(|&(x, y)| {
+                    placement.field[x + y * placement.width] != FieldState::Occupied(piece.id)
+                })
+        })
+    }
+
+    /// All solutions that contain the piece `fixed.0` in its variant `fixed.1` (an index into
+    /// `Piece::all_variants`) at `fixed.2`/`fixed.3`.
+    pub fn solve_from(
+        start: &Field,
+        pieces: &[Piece],
+        fixed: (u8, usize, usize, usize),
+    ) -> Vec<Field> {
+        let (id, variant_index, x, y) = fixed;
+        let placed = pieces
+            .iter()
+            .find(|piece| piece.id == id)
+            .and_then(|piece| piece.all_variants().into_iter().nth(variant_index))
+            .and_then(|variant| start.with_piece_placed(&variant, x, y));
+
+        match placed {
+            Some(placed) => {
+                let rest: Vec<Piece> = pieces.iter().filter(|p| p.id != id).cloned().collect();
+                Solution::new(&placed, &rest).solutions
+            }
+            None => vec![],
+        }
+    }
+
+    /// Like `new`, but stops at the first solution that covers the `max_possible_score`. The
+    /// objective is `Objective::Covered`, so that solution is the best one.
+    pub fn solve_until_perfect(start: &Field, pieces: &[Piece]) -> Solution {
+        Solution::solve_with(
+            start,
+            pieces,
+            &SolveOptions::default().stop_at_perfect(true),
+        )
+    }
+
+    /// The first solution whose `leftover_score` reaches `target`.
+    pub fn find_meeting_score(start: &Field, pieces: &[Piece], target: i32) -> Option<Field> {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        if pieces.is_empty() {
+            return Some(start.clone()).filter(|field| field.count() >= target);
+        }
+        let decorative = Solution::decorative_ids(&pieces);
+
+        let mut found = None;
+        Solution::search(
+            start,
+            &pieces,
+            &|_, _, _| true,
+            &mut SolveStats::default(),
+            &mut |field| {
+                if Solution::leftover_with(start, &decorative, &field) >= target {
+                    found = Some(field);
+                    false
+                } else {
+                    true
+                }
+            },
+        );
+
+        found
+    }
+
+    /// Whether all of `remaining_pieces` can still be placed on `current`. Stops at the first
+    /// solution.
+    pub fn is_still_solvable(current: &Field, remaining_pieces: &[Piece]) -> bool {
+        !Solution::collect(current, remaining_pieces, &|_, _, _| true, |_| false)
+            .solutions
+            .is_empty()
+    }
+
+    /// Takes the first of the `best_solutions` as the start field and solves it with
+    /// `more_pieces`, keeping the objective. Without any solution there is nothing to continue,
+    /// so the result has none either.
+    pub fn then_solve(&self, more_pieces: &[Piece]) -> Solution {
+        match self.best_solutions().first() {
+            Some(best) => Solution::solve_with(
+                best,
+                more_pieces,
+                &SolveOptions::default().objective(self.objective),
+            ),
+            None => Solution {
+                start: self.start.clone(),
+                pieces: more_pieces.iter().map(Piece::all_variants).collect(),
+                solutions: vec![],
+                stats: SolveStats::default(),
+                objective: self.objective,
+            },
+        }
+    }
+
+    /// Like `new`, but every piece may also be left out. Records every combination of placed
+    /// pieces, including the unchanged start field.
+    pub fn solve_optional(start: &Field, pieces: &[Piece]) -> Solution {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let mut solutions = vec![];
+        let mut stats = SolveStats::default();
+        Solution::search_optional(start, &pieces, &mut stats, &mut solutions);
+
+        Solution {
+            start: start.clone(),
+            pieces,
+            solutions,
+            stats,
+            objective: Objective::default(),
+        }
+    }
+
+    fn search_optional(
+        state: &Field,
+        remaining_pieces: &[Vec<Piece>],
+        stats: &mut SolveStats,
+        solutions: &mut Vec<Field>,
+    ) {
+        let (top, rest) = match remaining_pieces.split_first() {
+            Some(split) => split,
+            None => {
+                solutions.push(state.clone());
+                return;
+            }
+        };
+
+        Solution::search_optional(state, rest, stats, solutions);
+        stats.expanded += 1;
+
+        for piece in top.iter() {
+            for placement in state.place_iter(piece) {
+                stats.nodes += 1;
+                Solution::search_optional(&placement, rest, stats, solutions);
+            }
+        }
+    }
+
+    /// Like `new`, but remembers the partial boards that turned out to be dead ends. Reaching the
+    /// same occupied cells with the same remaining pieces again is skipped.
+    pub fn solve_memoized(start: &Field, pieces: &[Piece]) -> Solution {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let mut solutions = vec![];
+        let mut stats = SolveStats::default();
+        if pieces.is_empty() {
+            solutions.push(start.clone());
+        } else {
+            let mut dead_ends = HashSet::new();
+            Solution::search_memoized(start, &pieces, &mut dead_ends, &mut stats, &mut solutions);
+        }
+
+        Solution {
+            start: start.clone(),
+            pieces,
+            solutions,
+            stats,
+            objective: Objective::default(),
+        }
+    }
+
+    /// Returns whether at least one solution was found.
+    fn search_memoized(
+        state: &Field,
+        remaining_pieces: &[Vec<Piece>],
+        dead_ends: &mut HashSet<(usize, Vec<bool>)>,
+        stats: &mut SolveStats,
+        solutions: &mut Vec<Field>,
+    ) -> bool {
+        // the remaining pieces are always a suffix, so their number identifies them
+        let key = (
+            remaining_pieces.len(),
+            state
+                .field
+                .iter()
+                .map(|state| matches!(*state, FieldState::Free(_)))
+                .collect(),
+        );
+        if dead_ends.contains(&key) {
+            return false;
+        }
+
+        let top = &remaining_pieces[0];
+        let rest = &remaining_pieces[1..];
+        stats.expanded += 1;
+
+        let mut found = false;
+        for piece in top.iter() {
+            for placement in state.place_iter(piece) {
+                stats.nodes += 1;
+
+                if rest.is_empty() {
+                    solutions.push(placement);
+                    found = true;
+                } else if Solution::search_memoized(&placement, rest, dead_ends, stats, solutions) {
+                    found = true;
+                }
+            }
+        }
+
+        if !found {
+            dead_ends.insert(key);
+        }
+
+        found
+    }
+
+    /// Only keeps the solutions with the highest leftover score, i.e. `best_solutions` of `new`.
+    /// Partial boards that can't reach the best score found so far are skipped.
+    pub fn solve_best_only(start: &Field, pieces: &[Piece]) -> Solution {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let mut solutions = vec![];
+        let mut stats = SolveStats::default();
+        if pieces.is_empty() {
+            solutions.push(start.clone());
+        } else {
+            let decorative = Solution::decorative_ids(&pieces);
+            let mut best = None;
+            Solution::search_best_only(
+                start,
+                &decorative,
+                start,
+                &pieces,
+                &mut best,
+                &mut stats,
+                &mut solutions,
+            );
+            solutions.sort_by_cached_key(|field| Reverse(field.edge_contact()));
+        }
+
+        Solution {
+            start: start.clone(),
+            pieces,
+            solutions,
+            stats,
+            objective: Objective::default(),
+        }
+    }
+
+    fn search_best_only(
+        start: &Field,
+        decorative: &HashSet<u8>,
+        state: &Field,
+        remaining_pieces: &[Vec<Piece>],
+        best: &mut Option<i32>,
+        stats: &mut SolveStats,
+        solutions: &mut Vec<Field>,
+    ) {
+        // the remaining pieces that count cover at least the lowest scores of as many cells as
+        // they occupy
+        let cells: usize = remaining_pieces
+            .iter()
+            .filter(|p| !p[0].decorative)
+            .map(|p| p[0].occupied_count())
+            .sum();
+        let mut scores: Vec<i32> = state
+            .field
+            .iter()
+            .filter_map(|state| match *state {
+                FieldState::Free(score) => Some(i32::from(score)),
+                _ => None,
+            })
+            .collect();
+        scores.sort_unstable();
+        let leftover = Solution::leftover_with(start, decorative, state);
+        let bound = leftover - scores.iter().take(cells).sum::<i32>();
+        if best.is_some_and(|best| bound < best) {
+            return;
+        }
+
+        let top = &remaining_pieces[0];
+        let rest = &remaining_pieces[1..];
+        stats.expanded += 1;
+
+        for piece in top.iter() {
+            for placement in state.place_iter(piece) {
+                stats.nodes += 1;
+
+                if !rest.is_empty() {
+                    Solution::search_best_only(
+                        start, decorative, &placement, rest, best, stats, solutions,
+                    );
+                    continue;
+                }
+
+                let score = Solution::leftover_with(start, decorative, &placement);
+                match *best {
+                    Some(best) if score < best => continue,
+                    Some(best) if score == best => {}
+                    _ => {
+                        *best = Some(score);
+                        solutions.clear();
+                    }
+                }
+                solutions.push(placement);
+            }
+        }
+    }
+
+    /// All ways to place every piece when each free cell may be covered by up to `max_overlap`
+    /// pieces.
+    pub fn solve_layered(start: &Field, pieces: &[Piece], max_overlap: u8) -> Vec<LayeredField> {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let mut solutions = vec![];
+        Solution::search_layered(
+            &LayeredField::new(start.clone(), max_overlap),
+            &pieces,
+            &mut solutions,
+        );
+        solutions
+    }
+
+    fn search_layered(
+        state: &LayeredField,
+        remaining_pieces: &[Vec<Piece>],
+        solutions: &mut Vec<LayeredField>,
+    ) {
+        let (top, rest) = match remaining_pieces.split_first() {
+            Some(split) => split,
+            None => {
+                solutions.push(state.clone());
+                return;
+            }
+        };
+
+        for piece in top.iter() {
+            for placement in state.place_iter(piece) {
+                Solution::search_layered(&placement, rest, solutions);
+            }
+        }
+    }
+
+    /// Lazily yields all solutions with the lowest `leftover_score` first. The partial boards
+    /// are expanded best first, using `max_possible_score` of the remaining pieces as a bound.
+    pub fn iter_by_leftover_ascending(
+        start: &Field,
+        pieces: &[Piece],
+    ) -> impl Iterator<Item = Field> {
+        let variants: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let decorative = Solution::decorative_ids(&variants);
+        // the pieces that can still cover score after placing the first `depth` ones
+        let remaining: Vec<Vec<Piece>> = (0..=pieces.len())
+            .map(|depth| Solution::scoring_pieces(&pieces[depth..]))
+            .collect();
+        let origin = start.clone();
+        let bound = move |field: &Field, depth: usize| -> i32 {
+            Solution::leftover_with(&origin, &decorative, field)
+                - field.max_possible_score(&remaining[depth])
+        };
+
+        let mut heap = BinaryHeap::new();
+        let mut seq = 0;
+        heap.push(Node {
+            bound: bound(start, 0),
+            seq,
+            field: start.clone(),
+            depth: 0,
+        });
+
+        std::iter::from_fn(move || {
+            while let Some(node) = heap.pop() {
+                if node.depth == variants.len() {
+                    return Some(node.field);
+                }
+
+                for variant in variants[node.depth].iter() {
+                    for placement in node.field.place_iter(variant) {
+                        seq += 1;
+                        heap.push(Node {
+                            bound: bound(&placement, node.depth + 1),
+                            seq,
+                            field: placement,
+                            depth: node.depth + 1,
+                        });
+                    }
+                }
+            }
+
+            None
+        })
+    }
+
+    /// Records all solutions until `keep_going` returns false for one of them.
+    fn collect<F, S>(start: &Field, pieces: &[Piece], accept: &F, keep_going: S) -> Solution
+    where
+        F: Fn(&Field, &Piece, (usize, usize)) -> bool,
+        S: FnMut(&Field) -> bool,
+    {
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        Solution::collect_variants(start, &pieces, accept, keep_going)
+    }
+
+    /// Like `collect`, with the variants of the pieces already computed, e.g. shared between
+    /// several fields.
+    fn collect_variants<F, S>(
+        start: &Field,
+        pieces: &[Vec<Piece>],
+        accept: &F,
+        mut keep_going: S,
+    ) -> Solution
+    where
+        F: Fn(&Field, &Piece, (usize, usize)) -> bool,
+        S: FnMut(&Field) -> bool,
+    {
+        let mut solutions = vec![];
+        let mut stats = SolveStats::default();
+        if pieces.is_empty() {
+            solutions.push(start.clone());
+        } else {
+            Solution::search(start, pieces, accept, &mut stats, &mut |field| {
+                let go_on = keep_going(&field);
+                solutions.push(field);
+                go_on
+            });
+        }
+
+        Solution {
+            start: start.clone(),
+            pieces: pieces.to_vec(),
+            solutions,
+            stats,
+            objective: Objective::default(),
+        }
+    }
+
+    /// Depth first search over all placements, returns false if `on_solution` aborted it.
+    fn search<F, S>(
+        state: &Field,
+        remaining_pieces: &[Vec<Piece>],
+        accept: &F,
+        stats: &mut SolveStats,
+        on_solution: &mut S,
+    ) -> bool
+    where
+        F: Fn(&Field, &Piece, (usize, usize)) -> bool,
+        S: FnMut(Field) -> bool,
+    {
+        assert!(!remaining_pieces.is_empty());
+
+        let top = &remaining_pieces[0];
+        let rest = &remaining_pieces[1..];
+        stats.expanded += 1;
+
+        for piece in top.iter() {
+            for (placement, position) in state.place_iter(piece).with_positions() {
+                stats.nodes += 1;
+
+                if !accept(&placement, piece, position) {
+                    continue;
+                }
+
+                let go_on = if rest.is_empty() {
+                    on_solution(placement)
+                } else {
+                    Solution::search(&placement, rest, accept, stats, on_solution)
+                };
+
+                if !go_on {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Places the pieces one after another on their best single placement without any
+    /// backtracking. Pieces that don't fit anymore are skipped.
+    pub fn greedy(start: &Field, pieces: &[Piece]) -> Field {
+        pieces.iter().fold(
+            start.clone(),
+            |state, piece| match Solution::best_greedy_step(&state, &[piece.all_variants()]) {
+                Some((placement, _, _)) => placement,
+                None => state,
+            },
+        )
+    }
+
+    /// The single placement of any variant of any of `pieces` that covers the most score, like
+    /// `Field::best_single_placement` for several pieces. Returns the board, the id of the
+    /// placed piece and the covered score, which is signed as cells may score negative. Decorative
+    /// pieces cover a score of 0 and prefer the cells with the lowest scores, so they don't take
+    /// the cells away from the other pieces. Ties are resolved in favor of the first placement
+    /// found.
+    pub fn best_greedy_step(state: &Field, pieces: &[Vec<Piece>]) -> Option<(Field, u8, i32)> {
+        let score = state.count();
+        let mut best: Option<(Field, u8, (i32, i32))> = None;
+
+        for variant in pieces.iter().flatten() {
+            for placement in state.place_iter(variant) {
+                let covered = score - placement.count();
+                // the covered score, then the score a decorative piece takes away
+                let key = if variant.decorative {
+                    (0, -covered)
+                } else {
+                    (covered, 0)
+                };
+                if best.as_ref().is_none_or(|&(_, _, best_key)| key > best_key) {
+                    best = Some((placement, variant.id, key));
+                }
+            }
+        }
+
+        best.map(|(placement, id, (gained, _))| (placement, id, gained))
+    }
+
+    /// Number of distinct tilings, where pieces with the same shape are interchangeable.
+    pub fn tiling_count(start: &Field, pieces: &[Piece]) -> usize {
+        let canonicals: Vec<Piece> = pieces.iter().map(Piece::canonical).collect();
+        let shape_of: HashMap<u8, usize> = pieces
+            .iter()
+            .zip(canonicals.iter())
+            .map(|(piece, canonical)| {
+                let shape = canonicals.iter().position(|c| c == canonical).unwrap();
+                (piece.id, shape)
+            })
+            .collect();
+
+        // relabel the pieces in order of appearance, so that swapping two pieces of the same
+        // shape results in the same key, pieces already placed on `start` are the same in
+        // every solution and keep the label of a cell that isn't covered
+        let tilings: HashSet<Vec<Option<(usize, usize)>>> = Solution::new(start, pieces)
+            .solutions
+            .iter()
+            .map(|solution| {
+                let mut order: HashMap<u8, usize> = HashMap::new();
+                solution
+                    .field
+                    .iter()
+                    .zip(start.field.iter())
+                    .map(|(state, before)| match (state, before) {
+                        (FieldState::Occupied(_), FieldState::Occupied(_)) => None,
+                        (&FieldState::Occupied(id), _) => {
+                            let next = order.len();
+                            Some((shape_of[&id], *order.entry(id).or_insert(next)))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        tilings.len()
+    }
+
+    /// The combinations of `pieces` that exactly cover the free cells `region` of `start`, as
+    /// lists of piece ids ordered by their size. Every subset with the right number of cells is
+    /// searched, so this gets slow for many small pieces.
+    pub fn pieces_tiling_region(
+        start: &Field,
+        region: &[(usize, usize)],
+        pieces: &[Piece],
+    ) -> Vec<Vec<u8>> {
+        let mut board = Field {
+            width: start.width,
+            height: start.height,
+            field: vec![FieldState::Blocked; start.field.len()],
+            zones: start.zones.clone(),
+        };
+        for &(x, y) in region {
+            if x >= start.width || y >= start.height {
+                return vec![];
+            }
+            let i = x + y * start.width;
+            match start.field[i] {
+                FieldState::Free(_) => board.field[i] = start.field[i].clone(),
+                _ => return vec![],
+            }
+        }
+        let cells = board
+            .field
+            .iter()
+            .filter(|state| matches!(*state, FieldState::Free(_)))
+            .count();
+
+        let mut combinations = vec![];
+        Solution::tile_with_subsets(&board, cells, pieces, &mut vec![], &mut combinations);
+        combinations.sort_by_key(Vec::len);
+        combinations
+    }
+
+    /// Adds the subsets of `pieces` with exactly `cells` cells that tile `board`, each together
+    /// with `chosen`, to `combinations`. Subsets with too many cells aren't extended any further.
+    fn tile_with_subsets(
+        board: &Field,
+        cells: usize,
+        pieces: &[Piece],
+        chosen: &mut Vec<Piece>,
+        combinations: &mut Vec<Vec<u8>>,
+    ) {
+        if cells == 0 {
+            let found = Solution::collect(board, chosen, &|_, _, _| true, |_| false);
+            if !chosen.is_empty() && !found.solutions.is_empty() {
+                combinations.push(chosen.iter().map(|piece| piece.id).collect());
+            }
+            return;
+        }
+
+        for (i, piece) in pieces.iter().enumerate() {
+            if piece.occupied_count() <= cells {
+                chosen.push(piece.clone());
+                let rest = cells - piece.occupied_count();
+                Solution::tile_with_subsets(board, rest, &pieces[i + 1..], chosen, combinations);
+                chosen.pop();
+            }
+        }
+    }
+
+    /// Ids of a smallest subset of `all_pieces` that, with all of its pieces placed, covers
+    /// cells worth at least `target`, i.e. `start.count()` minus the `leftover_score`.
+    ///
+    /// The subsets are tried by ascending size and every one of them may run a full search, so
+    /// this costs up to `2^n` searches for `n` pieces.
+    pub fn min_pieces_for_score(
+        start: &Field,
+        all_pieces: &[Piece],
+        target: i32,
+    ) -> Option<Vec<u8>> {
+        let leftover = start.count() - target;
+
+        let mut found = None;
+        for size in 0..=all_pieces.len() {
+            for_each_combination(all_pieces.len(), size, |indices| {
+                let subset: Vec<Piece> = indices.iter().map(|&i| all_pieces[i].clone()).collect();
+                if Solution::can_leave_at_most(start, &subset, leftover) {
+                    found = Some(subset.iter().map(|piece| piece.id).collect());
+                }
+                found.is_none()
+            });
+            if found.is_some() {
+                break;
+            }
+        }
+
+        found
+    }
+
+    /// Whether placing all of `pieces` can leave a score of at most `leftover`.
+    fn can_leave_at_most(start: &Field, pieces: &[Piece], leftover: i32) -> bool {
+        if pieces.is_empty() {
+            return start.count() <= leftover;
+        }
+
+        let pieces: Vec<Vec<Piece>> = pieces.iter().map(Piece::all_variants).collect();
+        let decorative = Solution::decorative_ids(&pieces);
+        let mut found = false;
+        Solution::search(
+            start,
+            &pieces,
+            &|_, _, _| true,
+            &mut SolveStats::default(),
+            &mut |field| {
+                found = Solution::leftover_with(start, &decorative, &field) <= leftover;
+                !found
+            },
+        );
+        found
+    }
+
+    /// Number of legal placements of each piece, summed over all of its variants.
+    pub fn placement_counts(start: &Field, pieces: &[Piece]) -> Vec<(u8, usize)> {
+        pieces
+            .iter()
+            .map(|piece| {
+                let count = piece
+                    .all_variants()
+                    .iter()
+                    .map(|variant| start.place_iter(variant).count())
+                    .sum();
+                (piece.id, count)
+            })
+            .collect()
+    }
+
+    /// Number of distinct variants of each piece that appear in at least one solution.
+    pub fn used_variants(&self) -> Vec<(u8, usize)> {
+        self.pieces
+            .iter()
+            .map(|variants| {
+                let id = variants[0].id;
+                let used: HashSet<usize> = self
+                    .solutions
+                    .iter()
+                    .filter_map(|solution| Solution::placement_in(solution, variants))
+                    .map(|(variant, _, _)| variant)
+                    .collect();
+                (id, used.len())
+            })
+            .collect()
+    }
+
+    /// Every solution as the list of its placements `(id, variant, x, y)`, with the variant as
+    /// an index into `Piece::all_variants`, in the order of the pieces.
+    pub fn solution_placements(&self) -> Vec<Vec<(u8, usize, usize, usize)>> {
+        self.solutions
+            .iter()
+            .map(|solution| {
+                self.pieces
+                    .iter()
+                    .filter_map(|variants| {
+                        Solution::placement_in(solution, variants)
+                            .map(|(variant, x, y)| (variants[0].id, variant, x, y))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The variant and position of the piece with the `variants` on `solution`.
+    fn placement_in(solution: &Field, variants: &[Piece]) -> Option<(usize, usize, usize)> {
+        let id = variants[0].id;
+        let cells: Vec<(usize, usize)> = (0..solution.field.len())
+            .filter(|&i| solution.field[i] == FieldState::Occupied(id))
+            .map(|i| (i / solution.width, i % solution.width))
+            .collect();
+        let &(first_y, first_x) = cells.first()?;
+
+        variants.iter().enumerate().find_map(|(index, variant)| {
+            let offsets: Vec<(usize, usize)> = (0..variant.field.len())
+                .filter(|&i| variant.field[i].is_occupied())
+                .map(|i| (i / variant.width, i % variant.width))
+                .collect();
+            let &(offset_y, offset_x) = offsets.first()?;
+            if offsets.len() != cells.len() || first_x < offset_x || first_y < offset_y {
+                return None;
+            }
+
+            let (x, y) = (first_x - offset_x, first_y - offset_y);
+            let matches = offsets
+                .iter()
+                .zip(cells.iter())
+                .all(|(&(dy, dx), &cell)| (y + dy, x + dx) == cell);
+            if matches {
+                Some((index, x, y))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Free cells that only a single placement of a single piece covers, together with the id
+    /// of that piece, in row-major order.
+    pub fn forced_cells(start: &Field, pieces: &[Piece]) -> Vec<(usize, usize, u8)> {
+        let mut covering: Vec<(usize, Option<u8>)> = vec![(0, None); start.field.len()];
+        for piece in pieces.iter() {
+            for variant in piece.all_variants().iter() {
+                for placement in start.place_iter(variant) {
+                    for (i, state) in placement.field.iter().enumerate() {
+                        if *state != start.field[i] {
+                            covering[i] = (covering[i].0 + 1, Some(piece.id));
+                        }
+                    }
+                }
+            }
+        }
+
+        covering
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &(count, id))| match id {
+                Some(id) if count == 1 => Some((i % start.width, i / start.width, id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Ids of the pieces that can't be placed on `start` in any orientation.
+    pub fn unplaceable_pieces(start: &Field, pieces: &[Piece]) -> Vec<u8> {
+        pieces
+            .iter()
+            .filter(|piece| {
+                piece
+                    .all_variants()
+                    .iter()
+                    .all(|variant| start.place_iter(variant).next().is_none())
+            })
+            .map(|piece| piece.id)
+            .collect()
+    }
+
+    /// Errors with the ids of `pieces` that are already placed on `start`, in ascending order.
+    /// The solver and the renderers couldn't tell those pieces apart.
+    pub fn check_piece_ids(start: &Field, pieces: &[Piece]) -> Result<(), Vec<u8>> {
+        let placed = Solution::placed_ids(start);
+        let mut clashes: Vec<u8> = pieces
+            .iter()
+            .map(|piece| piece.id)
+            .filter(|id| placed.contains(id))
+            .collect();
+        clashes.sort_unstable();
+        clashes.dedup();
+
+        if clashes.is_empty() {
+            Ok(())
+        } else {
+            Err(clashes)
+        }
+    }
+
+    /// `pieces` with the ids that `check_piece_ids` reports replaced by the lowest ids used
+    /// neither on `start` nor by another piece. `None` if there aren't enough free ids.
+    pub fn remap_piece_ids(start: &Field, pieces: &[Piece]) -> Option<Vec<Piece>> {
+        let placed = Solution::placed_ids(start);
+        let mut used: HashSet<u8> = pieces.iter().map(|piece| piece.id).collect();
+        used.extend(placed.iter());
+
+        let mut free_ids = (0..=u8::MAX).filter(|id| !used.contains(id));
+        pieces
+            .iter()
+            .map(|piece| {
+                let mut piece = piece.clone();
+                if placed.contains(&piece.id) {
+                    piece.id = free_ids.next()?;
+                }
+                Some(piece)
+            })
+            .collect()
+    }
+
+    fn placed_ids(field: &Field) -> HashSet<u8> {
+        field
+            .field
+            .iter()
+            .filter_map(|state| match *state {
+                FieldState::Occupied(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// All placements of every variant of the piece `piece_id` on the start field.
+    pub fn placements_for(&self, piece_id: u8) -> Vec<Field> {
+        self.pieces
+            .iter()
+            .filter(|variants| variants[0].id == piece_id)
+            .flatten()
+            .flat_map(|variant| self.start.place_iter(variant))
+            .collect()
+    }
+
+    /// The distinct boards reachable from `state` by placing one variant of one of `pieces`,
+    /// each with the id of the placed piece, in the order of their first occurrence.
+    pub fn successors(state: &Field, pieces: &[Vec<Piece>]) -> Vec<(Field, u8)> {
+        let mut seen = HashSet::new();
+        pieces
+            .iter()
+            .flatten()
+            .flat_map(|variant| {
+                state
+                    .place_iter(variant)
+                    .map(move |board| (board, variant.id))
+            })
+            .filter(|(board, _)| seen.insert(board.clone()))
+            .collect()
+    }
+
+    /// The distinct solutions in the order of their first occurrence. The boards are hashed on
+    /// all available cores, only boards with equal hashes are compared afterwards.
+    pub fn unique_solutions_parallel(&self) -> Vec<Field> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = self.solutions.len().div_ceil(threads).max(1);
+
+        let hashes: Vec<u64> = thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .solutions
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|field| {
+                                let mut hasher = DefaultHasher::new();
+                                field.hash(&mut hasher);
+                                hasher.finish()
+                            })
+                            .collect::<Vec<u64>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+        let mut unique = vec![];
+        for (i, hash) in hashes.into_iter().enumerate() {
+            let candidates = seen.entry(hash).or_default();
+            if candidates
+                .iter()
+                .all(|&j| self.solutions[j] != self.solutions[i])
+            {
+                candidates.push(i);
+                unique.push(self.solutions[i].clone());
+            }
+        }
+
+        unique
+    }
+
+    /// Up to `n` distinct recorded solutions, picked uniformly at random and returned in their
+    /// recorded order. The same `seed` always picks the same solutions.
+    pub fn sample(&self, n: usize, seed: u64) -> Vec<Field> {
+        let mut rng = Rng(seed);
+        let mut indices: Vec<usize> = (0..self.solutions.len()).collect();
+        let n = n.min(indices.len());
+
+        // partial Fisher-Yates shuffle
+        for i in 0..n {
+            let j = i + rng.below(indices.len() - i);
+            indices.swap(i, j);
+        }
+        indices.truncate(n);
+        indices.sort_unstable();
+
+        indices.iter().map(|&i| self.solutions[i].clone()).collect()
+    }
+
+    /// Counts and score statistics of the recorded solutions.
+    pub fn metrics(&self) -> SolutionMetrics {
+        if self.solutions.is_empty() {
+            return SolutionMetrics::default();
+        }
+
+        let distinct: HashSet<&Field> = self.solutions.iter().collect();
+        let coverage: HashSet<Vec<bool>> = self
+            .solutions
+            .iter()
+            .map(|solution| {
+                solution
+                    .field
+                    .iter()
+                    .map(|state| matches!(*state, FieldState::Occupied(_)))
+                    .collect()
+            })
+            .collect();
+
+        let scores: Vec<i32> = self
+            .solutions
+            .iter()
+            .map(|solution| self.leftover_score(solution))
+            .collect();
+        let n = scores.len() as f64;
+        let mean = scores.iter().map(|&s| f64::from(s)).sum::<f64>() / n;
+        let variance = scores
+            .iter()
+            .map(|&s| (f64::from(s) - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        SolutionMetrics {
+            solutions: self.solutions.len(),
+            distinct_solutions: distinct.len(),
+            coverage_classes: coverage.len(),
+            min_score: *scores.iter().min().unwrap(),
+            max_score: *scores.iter().max().unwrap(),
+            mean_score: mean,
+            score_deviation: variance.sqrt(),
+        }
+    }
+
+    /// Number of recorded solutions per `leftover_score`.
+    pub fn score_histogram(&self) -> BTreeMap<i32, usize> {
+        let mut histogram = BTreeMap::new();
+        for solution in self.solutions.iter() {
+            *histogram.entry(self.leftover_score(solution)).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// The recorded solutions in which a piece covers `x`/`y`.
+    pub fn solutions_covering_cell(&self, x: usize, y: usize) -> Vec<Field> {
+        self.solutions
+            .iter()
+            .filter(|solution| solution.piece_at(x, y).is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// `b / s` for the average branching factor `b = nodes / expanded` of the search and the
+    /// number of solutions `s`. Few solutions and, for a given number of solutions, a wide search
+    /// tree make a puzzle harder. Infinite for unsolvable puzzles, 0 without any search.
+    pub fn difficulty(&self) -> f64 {
+        if self.solutions.is_empty() {
+            return f64::INFINITY;
+        }
+        if self.stats.expanded == 0 {
+            return 0.0;
+        }
+
+        let branching = self.stats.nodes as f64 / self.stats.expanded as f64;
+        branching / self.solutions.len() as f64
+    }
+
+    /// The highest leftover score, see `leftover_score`.
+    pub fn highest_score(&self) -> i32 {
+        self.solutions
+            .iter()
+            .map(|f| self.leftover_score(f))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// `field.count()`, but cells covered by decorative pieces count as if they were free.
+    pub fn leftover_score(&self, field: &Field) -> i32 {
+        Solution::leftover_with(&self.start, &Solution::decorative_ids(&self.pieces), field)
+    }
+
+    fn decorative_ids(pieces: &[Vec<Piece>]) -> HashSet<u8> {
+        pieces
+            .iter()
+            .map(|variants| &variants[0])
+            .filter(|piece| piece.decorative)
+            .map(|piece| piece.id)
+            .collect()
+    }
+
+    /// The pieces that aren't decorative, i.e. the ones that can cover score.
+    fn scoring_pieces(pieces: &[Piece]) -> Vec<Piece> {
+        pieces
+            .iter()
+            .filter(|piece| !piece.decorative)
+            .cloned()
+            .collect()
+    }
+
+    /// `leftover_score` of `field` for the start field `start`.
+    fn leftover_with(start: &Field, decorative: &HashSet<u8>, field: &Field) -> i32 {
+        if decorative.is_empty() {
+            return field.count();
+        }
+
+        let covered: i32 = field
+            .field
+            .iter()
+            .zip(start.field.iter())
+            .map(|(state, start)| match (state, start) {
+                (FieldState::Occupied(id), FieldState::Free(score)) if decorative.contains(id) => {
+                    i32::from(*score)
+                }
+                _ => 0,
+            })
+            .sum();
+        field.count() + covered
+    }
+
+    /// All solutions ordered by descending `leftover_score`, keeping the discovery order for
+    /// equal scores.
+    pub fn solutions_by_score(&self) -> Vec<Field> {
+        let decorative = Solution::decorative_ids(&self.pieces);
+        let mut solutions = self.solutions.clone();
+        solutions.sort_by_cached_key(|field| {
+            Reverse(Solution::leftover_with(&self.start, &decorative, field))
+        });
+        solutions
+    }
+
+    /// The `n` solutions with the highest scores, see `solutions_by_score`.
+    pub fn top_n(&self, n: usize) -> Vec<Field> {
+        let mut solutions = self.solutions_by_score();
+        solutions.truncate(n);
+        solutions
+    }
+
+    /// Score of `field` under the objective of the solution.
+    pub fn objective_score(&self, field: &Field) -> i64 {
+        let leftover = i64::from(self.leftover_score(field));
+        let placed = || -> Vec<&Piece> {
+            let ids: HashSet<u8> = field
+                .field
+                .iter()
+                .filter_map(|state| match *state {
+                    FieldState::Occupied(id) => Some(id),
+                    _ => None,
+                })
+                .collect();
+            self.pieces
+                .iter()
+                .map(|variants| &variants[0])
+                .filter(|piece| ids.contains(&piece.id) && !piece.decorative)
+                .collect()
+        };
+
+        match self.objective {
+            Objective::Leftover => leftover,
+            Objective::Covered => i64::from(self.start.count()) - leftover,
+            Objective::Weighted => {
+                leftover
+                    + placed()
+                        .iter()
+                        .map(|piece| i64::from(piece.weight))
+                        .sum::<i64>()
+            }
+            Objective::MaxPieces => ((placed().len() as i64) << 32) + leftover,
+        }
+    }
+
+    /// The solutions with the highest `objective_score`, tightly packed ones with a higher
+    /// `edge_contact` first.
+    pub fn best_solutions(&self) -> Vec<Field> {
+        let mut solutions = self.best_by(|field| self.objective_score(field));
+        solutions.sort_by_cached_key(|field| Reverse(field.edge_contact()));
+        solutions
+    }
+
+    /// The solutions with the highest score according to `score_fn`.
+    pub fn best_by<F>(&self, score_fn: F) -> Vec<Field>
+    where
+        F: Fn(&Field) -> i64,
+    {
+        let scores: Vec<i64> = self.solutions.iter().map(score_fn).collect();
+        let highest_score = match scores.iter().max() {
+            Some(&score) => score,
+            None => return vec![],
+        };
+        self.solutions
+            .iter()
+            .zip(scores)
+            .filter(|&(_, score)| score == highest_score)
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+
+    /// Compact, shareable code of the placements on `field`.
+    ///
+    /// Every non-blocked cell contributes a digit (0 for free, id + 1 for occupied) of a
+    /// number that is written in base 36, prefixed by the largest digit and a `.`.
+    pub fn encode(field: &Field) -> String {
+        let values: Vec<u32> = field
+            .field
+            .iter()
+            .filter_map(|state| match *state {
+                FieldState::Blocked => None,
+                FieldState::Free(_) => Some(0),
+                FieldState::Occupied(id) => Some(u32::from(id) + 1),
+            })
+            .collect();
+        let max = values.iter().cloned().max().unwrap_or(0).max(1);
+
+        // little endian base 36 digits
+        let mut digits: Vec<u32> = vec![];
+        for value in values {
+            let mut carry = value;
+            for digit in digits.iter_mut() {
+                let v = *digit * (max + 1) + carry;
+                *digit = v % 36;
+                carry = v / 36;
+            }
+            while carry > 0 {
+                digits.push(carry % 36);
+                carry /= 36;
+            }
+        }
+
+        let to_char = |d: u32| std::char::from_digit(d, 36).unwrap();
+        let mut header = vec![];
+        let mut n = max;
+        while n > 0 {
+            header.push(to_char(n % 36));
+            n /= 36;
+        }
+
+        let mut code: String = header.into_iter().rev().collect();
+        code.push('.');
+        if digits.is_empty() {
+            code.push('0');
+        }
+        code.extend(digits.into_iter().rev().map(to_char));
+        code
+    }
+
+    /// Reverses `encode`, taking the blocked cells and scores from `template`.
+    pub fn decode(code: &str, template: &Field) -> Result<Field, ParseError> {
+        let mut parts = code.splitn(2, '.');
+        let max = parts
+            .next()
+            .and_then(|header| u32::from_str_radix(header, 36).ok())
+            .filter(|max| (1..=256).contains(max))
+            .ok_or(ParseError::InvalidCode)?;
+        let body = parts
+            .next()
+            .filter(|body| !body.is_empty())
+            .ok_or(ParseError::InvalidCode)?;
+
+        // big endian base 36 digits
+        let mut digits = body
+            .chars()
+            .map(|c| c.to_digit(36))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(ParseError::InvalidCode)?;
+
+        let mut result = template.clone();
+        for state in result.field.iter_mut().rev() {
+            if let FieldState::Blocked = *state {
+                continue;
+            }
+
+            let mut remainder = 0;
+            for digit in digits.iter_mut() {
+                let v = remainder * 36 + *digit;
+                *digit = v / (max + 1);
+                remainder = v % (max + 1);
+            }
+
+            if remainder > 0 {
+                *state = FieldState::Occupied((remainder - 1) as u8);
+            }
+        }
+
+        if digits.iter().any(|&digit| digit != 0) {
+            return Err(ParseError::InvalidCode);
+        }
+
+        Ok(result)
+    }
+}
+
+/// One line summary like `solutions=42 best_score=17 pieces=5 field=5x4`.
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "solutions={} best_score={} pieces={} field={}x{}",
+            self.solutions.len(),
+            self.highest_score(),
+            self.pieces.len(),
+            self.start.width,
+            self.start.height
+        )
+    }
+}
+
+/// Calls `f` with the indices of every subset of `0..n` with `size` elements in lexicographic
+/// order, until it returns false.
+fn for_each_combination<F: FnMut(&[usize]) -> bool>(n: usize, size: usize, mut f: F) {
+    if size > n {
+        return;
+    }
+
+    let mut indices: Vec<usize> = (0..size).collect();
+    loop {
+        if !f(&indices) {
+            return;
+        }
+        // the last index that can still be moved to the right
+        match (0..size).rev().find(|&i| indices[i] < n - size + i) {
+            Some(i) => {
+                indices[i] += 1;
+                for j in i + 1..size {
+                    indices[j] = indices[j - 1] + 1;
+                }
+            }
+            None => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(s: &str) -> Field {
+        s.parse().unwrap()
+    }
+
+    fn piece(s: &str, id: u8) -> Piece {
+        let mut piece: Piece = s.parse().unwrap();
+        piece.id = id;
+        piece
+    }
+
+    /// The puzzle of the `3` directory.
+    fn puzzle_3() -> (Field, Vec<Piece>) {
+        (
+            field("  -2-\n 4---5\n-------\n1-----3"),
+            vec![
+                piece("XX\n XX\n X", 0),
+                piece("XX\nX\nXX", 1),
+                piece("X\nXXX\nX", 2),
+            ],
+        )
+    }
+
+    #[test]
+    fn placements_for_only_places_the_requested_piece() {
+        let solution = Solution::new(&field("---\n---"), &[piece("XX", 0), piece("X\nX", 1)]);
+        let placements = solution.placements_for(1);
+
+        // 3 vertical and 4 horizontal placements of the domino
+        assert_eq!(placements.len(), 7);
+        for placement in placements.iter() {
+            assert!(placement.field.contains(&FieldState::Occupied(1)));
+            assert!(!placement.field.contains(&FieldState::Occupied(0)));
+        }
+        assert!(solution.placements_for(7).is_empty());
+    }
+
+    #[test]
+    fn unplaceable_pieces_lists_pieces_that_fit_nowhere() {
+        let pieces = [piece("XX", 0), piece("XXX", 1), piece("X\nX\nX", 2)];
+        assert_eq!(
+            Solution::unplaceable_pieces(&field("--\n--"), &pieces),
+            vec![1, 2]
+        );
+        assert!(Solution::unplaceable_pieces(&field("---"), &pieces[..2]).is_empty());
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        let (start, pieces) = puzzle_3();
+        let solution = Solution::new(&start, &pieces);
+        assert!(!solution.solutions.is_empty());
+        for board in solution.solutions.iter() {
+            let code = Solution::encode(board);
+            assert_eq!(&Solution::decode(&code, &start).unwrap(), board);
+        }
+        assert_eq!(
+            Solution::decode(&Solution::encode(&start), &start).unwrap(),
+            start
+        );
+    }
+
+    #[test]
+    fn decode_rejects_invalid_codes() {
+        let (start, _) = puzzle_3();
+        assert!(matches!(
+            Solution::decode("2.zzzzzzzzzzzzzzzzzz", &start),
+            Err(ParseError::InvalidCode)
+        ));
+        assert!(Solution::decode("x", &start).is_err());
+    }
+
+    #[test]
+    fn greedy_places_pieces_without_beating_the_full_search() {
+        let (start, pieces) = puzzle_3();
+        let board = Solution::greedy(&start, &pieces);
+        assert!(board.validate_against(&start).is_ok());
+        assert!(board.count() <= Solution::new(&start, &pieces).highest_score());
+        // the first domino takes the 9, the second one can't be placed anymore
+        assert_eq!(
+            Solution::greedy(&field("9-"), &[piece("XX", 0), piece("XX", 1)]).to_string(),
+            "AA"
+        );
+    }
+
+    #[test]
+    fn solve_region_leaves_cells_outside_of_the_region_untouched() {
+        let start = field("-----\n-----\n-----");
+        let solution =
+            Solution::solve_region(&start, &[piece("XX", 0), piece("X", 1)], (1, 0, 3, 2));
+        // 4 domino placements in the 2x2 region, each leaving 2 cells for the monomino
+        assert_eq!(solution.solutions.len(), 8);
+        for board in solution.solutions.iter() {
+            for (i, state) in board.field.iter().enumerate() {
+                let (x, y) = (i % 5, i / 5);
+                if !(1..3).contains(&x) || y >= 2 {
+                    assert_eq!(*state, FieldState::Free(0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tiling_count_treats_equal_shapes_as_interchangeable() {
+        let start = field("---\n---");
+        let pieces = [piece("XX", 0), piece("X", 1), piece("X", 2), piece("XX", 3)];
+        let solution_count = Solution::new(&start, &pieces).solutions.len();
+        // both the dominoes and the monominoes can be swapped
+        assert_eq!(Solution::tiling_count(&start, &pieces) * 4, solution_count);
+    }
+
+    #[test]
+    fn tiling_count_accepts_pre_placed_pieces() {
+        let start = field("Z--\n---");
+        let pieces = [piece("XX", 0), piece("X", 1), piece("XX", 2)];
+        let solution_count = Solution::new(&start, &pieces).solutions.len();
+        assert!(solution_count > 0);
+        assert_eq!(Solution::tiling_count(&start, &pieces) * 2, solution_count);
+    }
+
+    #[test]
+    fn no_pieces_leave_the_start_field_as_the_only_solution() {
+        let solution = Solution::new(&field("-1-"), &[]);
+        assert_eq!(solution.solutions, vec![field("-1-")]);
+        assert_eq!(solution.highest_score(), 1);
+    }
+
+    #[test]
+    fn placement_counts_sums_over_all_variants() {
+        // 3 horizontal and 4 vertical domino placements, 6 cells for the monomino
+        assert_eq!(
+            Solution::placement_counts(&field("---\n---"), &[piece("XX", 0), piece("X", 4)]),
+            vec![(0, 7), (4, 6)]
+        );
+    }
+
+    #[test]
+    fn solve_with_filter_rejects_placements() {
+        let start = field("---\n---");
+        let pieces = [piece("XX", 0), piece("X", 1), piece("X", 2), piece("XX", 3)];
+        let never_cover_the_middle = Solution::solve_with_filter(&start, &pieces, |field, _, _| {
+            !matches!(field.field[4], FieldState::Occupied(_))
+        });
+        assert!(never_cover_the_middle.solutions.is_empty());
+
+        let filtered = Solution::solve_with_filter(&start, &pieces, |field, piece, position| {
+            let blocked = piece.id == 1 && position == (0, 0);
+            !blocked && field.field[4] != FieldState::Occupied(3)
+        });
+        assert!(!filtered.solutions.is_empty());
+        for solution in &filtered.solutions {
+            assert_ne!(solution.field[0], FieldState::Occupied(1));
+            assert_ne!(solution.field[4], FieldState::Occupied(3));
+        }
+        assert!(filtered.solutions.len() < Solution::new(&start, &pieces).solutions.len());
+    }
+
+    #[test]
+    fn solutions_by_score_starts_with_the_best_solution() {
+        let (start, pieces) = puzzle_3();
+        let solution = Solution::new(&start, &pieces[..2]);
+        let sorted = solution.solutions_by_score();
+        assert_eq!(sorted.len(), solution.solutions.len());
+        assert_eq!(sorted[0].count(), solution.highest_score());
+        assert!(sorted.windows(2).all(|w| w[0].count() >= w[1].count()));
+    }
+
+    #[test]
+    fn solve_until_perfect_stops_at_a_perfect_score() {
+        let start = field("1-2\n-3-\n---");
+        let pieces = [piece("XXX", 0), piece("XXX", 1), piece("XXX", 2)];
+        assert_eq!(start.max_possible_score(&pieces), 6);
+
+        let full = Solution::new(&start, &pieces);
+        let perfect = Solution::solve_until_perfect(&start, &pieces);
+        assert_eq!(perfect.solutions.last().unwrap().count(), 0);
+        assert!(perfect.stats.nodes < full.stats.nodes);
+
+        assert_eq!(perfect.objective, Objective::Covered);
+        assert_eq!(perfect.best_solutions()[0].count(), 0);
+        assert!(perfect
+            .best_solutions()
+            .contains(perfect.solutions.last().unwrap()));
+    }
+
+    #[test]
+    fn solve_until_perfect_searches_everything_without_a_perfect_solution() {
+        let start = field("1-2\n-3-\n---");
+        let pieces = [piece("XXX", 0)];
+        assert_eq!(
+            Solution::solve_until_perfect(&start, &pieces)
+                .solutions
+                .len(),
+            Solution::new(&start, &pieces).solutions.len()
+        );
+    }
+
+    #[test]
+    fn find_meeting_score_finds_the_highest_score_but_not_more() {
+        let (start, pieces) = puzzle_3();
+        let highest = Solution::new(&start, &pieces).highest_score();
+        let found = Solution::find_meeting_score(&start, &pieces, highest).unwrap();
+        assert!(found.count() >= highest);
+        assert!(Solution::find_meeting_score(&start, &pieces, highest + 1).is_none());
+    }
+
+    #[test]
+    fn display_summarizes_the_solution() {
+        let (start, pieces) = puzzle_3();
+        assert_eq!(
+            Solution::new(&start, &pieces).to_string(),
+            "solutions=328 best_score=12 pieces=3 field=7x4"
+        );
+    }
+
+    #[test]
+    fn solve_optional_allows_leaving_pieces_out() {
+        let mut domino = piece("XX", 0);
+        domino.weight = 20;
+        let mut solution = Solution::solve_optional(&field("19"), &[domino]);
+        assert_eq!(solution.solutions.len(), 2);
+        assert_eq!(solution.best_solutions().len(), 1);
+        assert_eq!(solution.best_solutions()[0].count(), 10);
+
+        solution.objective = Objective::Weighted;
+        let best = solution.best_solutions();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].count(), 0);
+        assert_eq!(solution.objective_score(&best[0]), 20);
+    }
+
+    #[test]
+    fn solve_memoized_finds_the_same_solutions_with_fewer_nodes() {
+        let start = field("-----\n-----\n---- ");
+        let pieces = [
+            piece("X", 0),
+            piece("X", 1),
+            piece("XXX\nX", 2),
+            piece("XX\nXX", 3),
+            piece("XXX\n  X", 4),
+        ];
+        let plain = Solution::new(&start, &pieces);
+        let memoized = Solution::solve_memoized(&start, &pieces);
+        assert_eq!(plain.solutions.len(), memoized.solutions.len());
+        let plain_set: HashSet<&Field> = plain.solutions.iter().collect();
+        assert!(memoized
+            .solutions
+            .iter()
+            .all(|solution| plain_set.contains(solution)));
+        assert!(memoized.stats.nodes < plain.stats.nodes);
+    }
+
+    #[test]
+    fn solve_with_forbidden_keeps_pieces_off_their_cells() {
+        let forbidden: HashMap<u8, Vec<(usize, usize)>> =
+            vec![(0, vec![(0, 0)])].into_iter().collect();
+        let solution = Solution::solve_with_forbidden(
+            &field("---"),
+            &[piece("XX", 0), piece("X", 1)],
+            &forbidden,
+        );
+        assert_eq!(solution.solutions, vec![field("BAA")]);
+    }
+
+    #[test]
+    fn solve_with_forbidden_ignores_cells_outside_of_the_field() {
+        let forbidden: HashMap<u8, Vec<(usize, usize)>> =
+            vec![(0, vec![(3, 0), (0, 5)])].into_iter().collect();
+        let pieces = [piece("XX", 0), piece("X", 1)];
+        let solution = Solution::solve_with_forbidden(&field("---"), &pieces, &forbidden);
+        assert_eq!(
+            solution.solutions.len(),
+            Solution::new(&field("---"), &pieces).solutions.len()
+        );
+    }
+
+    #[test]
+    fn best_by_ranks_with_the_given_score() {
+        let solution = Solution::new(&field("---\n---"), &[piece("XXX", 0), piece("XXX", 1)]);
+        let a_on_top = solution.best_by(|field| {
+            field.field[..3]
+                .iter()
+                .all(|state| *state == FieldState::Occupied(0)) as i64
+        });
+        assert_eq!(a_on_top, vec![field("AAA\nBBB")]);
+        assert_eq!(solution.best_solutions().len(), 2);
+    }
+
+    #[test]
+    fn solve_from_keeps_the_fixed_piece_in_place() {
+        let (start, pieces) = puzzle_3();
+        let variant = &pieces[1].all_variants()[2];
+        let pinned = start.with_piece_placed(variant, 0, 2).unwrap();
+        let solutions = Solution::solve_from(&start, &pieces, (1, 2, 0, 2));
+        assert!(!solutions.is_empty());
+        for solution in &solutions {
+            for (cell, pinned_cell) in solution.field.iter().zip(pinned.field.iter()) {
+                if *pinned_cell == FieldState::Occupied(1) {
+                    assert_eq!(cell, pinned_cell);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solve_from_rejects_impossible_placements() {
+        let (start, pieces) = puzzle_3();
+        assert!(Solution::solve_from(&start, &pieces, (1, 2, 6, 3)).is_empty());
+        assert!(Solution::solve_from(&start, &pieces, (1, 20, 0, 2)).is_empty());
+    }
+
+    #[test]
+    fn top_n_returns_the_best_solutions_first() {
+        let (start, pieces) = puzzle_3();
+        let solution = Solution::new(&start, &pieces[..2]);
+        let top = solution.top_n(50);
+        assert_eq!(top.len(), 50);
+        assert_eq!(top[0].count(), solution.highest_score());
+        assert!(top.windows(2).all(|w| w[0].count() >= w[1].count()));
+        assert!(top[49].count() < solution.highest_score());
+        assert_eq!(solution.top_n(100_000).len(), solution.solutions.len());
+    }
+
+    #[test]
+    fn max_pieces_prefers_solutions_with_more_pieces() {
+        let mut solution = Solution::solve_optional(
+            &field("9---"),
+            &[piece("XXX", 0), piece("XX", 1), piece("XX", 2)],
+        );
+        solution.objective = Objective::MaxPieces;
+        assert_eq!(
+            solution.best_solutions(),
+            vec![field("BBCC"), field("CCBB")]
+        );
+
+        solution.objective = Objective::Leftover;
+        assert_eq!(solution.best_solutions()[0].count(), 9);
+    }
+
+    #[test]
+    fn covered_counts_negative_scores_of_covered_cells() {
+        let mut start = field("---");
+        start.apply_score_overlay("-3 . 2").unwrap();
+        assert_eq!(start.count(), -1);
+
+        let mut solution = Solution::new(&start, &[piece("XX", 0)]);
+        solution.objective = Objective::Covered;
+        let best = solution.best_solutions();
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].field[0], FieldState::Free(-3));
+        assert_eq!(solution.objective_score(&best[0]), 2);
+    }
+
+    #[test]
+    fn iter_by_leftover_ascending_yields_the_lowest_leftover_first() {
+        let (start, pieces) = puzzle_3();
+        let solution_count = Solution::new(&start, &pieces).solutions.len();
+        let ascending: Vec<Field> = Solution::iter_by_leftover_ascending(&start, &pieces).collect();
+        assert_eq!(ascending.len(), solution_count);
+        assert!(ascending.windows(2).all(|w| w[0].count() <= w[1].count()));
+    }
+
+    #[test]
+    fn iter_by_leftover_ascending_yields_the_start_without_pieces() {
+        let start = field("-1-");
+        assert_eq!(
+            Solution::iter_by_leftover_ascending(&start, &[]).collect::<Vec<_>>(),
+            vec![start]
+        );
+    }
+
+    #[test]
+    fn used_variants_counts_the_orientations_in_the_solutions() {
+        let start = field("----\n----\n----\n----");
+        let solution = Solution::new(&start, &[piece("XX\nXX", 0), piece("XXX\nX", 1)]);
+        let used = solution.used_variants();
+        assert_eq!(used[0], (0, 1));
+        assert_eq!(used[1].0, 1);
+        assert_eq!(used[1].1, 8);
+    }
+
+    #[test]
+    fn metrics_summarizes_the_solutions() {
+        let solution = Solution::new(&field("12-\n---"), &[piece("XX", 0), piece("X", 1)]);
+        let metrics = solution.metrics();
+        // 7 domino placements, each leaving 4 cells for the monomino
+        assert_eq!((metrics.solutions, metrics.distinct_solutions), (28, 28));
+        // boards that only differ in which piece covers a cell share a class
+        assert_eq!(metrics.coverage_classes, 18);
+        assert_eq!((metrics.min_score, metrics.max_score), (0, 3));
+        assert!((metrics.mean_score - 39.0 / 28.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn metrics_without_solutions_are_zero() {
+        let solution = Solution::new(&field("-"), &[piece("XX", 0)]);
+        assert_eq!(solution.metrics(), SolutionMetrics::default());
+    }
+
+    #[test]
+    fn solve_best_only_finds_the_best_solutions_with_fewer_nodes() {
+        let (start, pieces) = puzzle_3();
+        let full = Solution::new(&start, &pieces);
+        let best_only = Solution::solve_best_only(&start, &pieces);
+        assert_eq!(full.best_solutions(), best_only.solutions);
+        assert!(best_only.stats.nodes < full.stats.nodes);
+
+        let start = field("1-2\n-3-\n4-5");
+        let pieces = [piece("XX", 0), piece("X\nX", 1)];
+        assert_eq!(
+            Solution::new(&start, &pieces).best_solutions(),
+            Solution::solve_best_only(&start, &pieces).solutions
+        );
+    }
+
+    #[test]
+    fn solve_best_only_without_solutions() {
+        assert!(Solution::solve_best_only(&field("-"), &[piece("XX", 0)])
+            .solutions
+            .is_empty());
+    }
+
+    #[test]
+    fn pieces_tiling_region_lists_the_exact_covers() {
+        let start = field("----\n----");
+        let pieces = [
+            piece("XX", 0),
+            piece("XX", 1),
+            piece("XXXX", 2),
+            piece("XX\nXX", 3),
+            piece("X", 4),
+        ];
+        let row = [(0, 0), (1, 0), (2, 0), (3, 0)];
+        assert_eq!(
+            Solution::pieces_tiling_region(&start, &row, &pieces),
+            vec![vec![2], vec![0, 1]]
+        );
+        let square = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        assert_eq!(
+            Solution::pieces_tiling_region(&start, &square, &pieces),
+            vec![vec![3], vec![0, 1]]
+        );
+        assert!(Solution::pieces_tiling_region(&field(" ---"), &row, &pieces).is_empty());
+    }
+
+    #[test]
+    fn pieces_tiling_region_supports_more_than_64_pieces() {
+        let start = field("--");
+        let pieces: Vec<Piece> = (0..70)
+            .map(|id| piece(if id == 69 { "XX" } else { "XXX" }, id))
+            .collect();
+        assert_eq!(
+            Solution::pieces_tiling_region(&start, &[(0, 0), (1, 0)], &pieces),
+            vec![vec![69]]
+        );
+    }
+
+    #[test]
+    fn solutions_covering_cell_filters_by_the_cell() {
+        let solution = Solution::new(&field("12-\n---"), &[piece("XX", 0), piece("X", 1)]);
+        let covering = solution.solutions_covering_cell(1, 0);
+        let expected: Vec<Field> = solution
+            .solutions
+            .iter()
+            .filter(|field| field.piece_at(1, 0).is_some())
+            .cloned()
+            .collect();
+        assert!(!covering.is_empty() && covering.len() < solution.solutions.len());
+        assert_eq!(covering, expected);
+        assert!(solution.solutions_covering_cell(9, 9).is_empty());
+    }
+
+    #[test]
+    fn difficulty_grows_with_the_constraints() {
+        let tight = Solution::new(&field("--\n--"), &[piece("XX", 0), piece("XX", 1)]);
+        let loose = Solution::new(
+            &field("----\n----\n----\n----"),
+            &[piece("X", 0), piece("X", 1)],
+        );
+        assert!(tight.difficulty() > loose.difficulty());
+        assert!(Solution::new(&field("-"), &[piece("XX", 0)])
+            .difficulty()
+            .is_infinite());
+        assert_eq!(Solution::new(&field("-"), &[]).difficulty(), 0.0);
+    }
+
+    #[test]
+    fn solve_with_anchor_score_places_anchors_on_the_score() {
+        let start = field("1-3\n---");
+        let anchored =
+            Solution::solve_with_anchor_score(&start, &[piece("O", 0), piece("XX", 1)], 3);
+        assert!(!anchored.solutions.is_empty());
+        assert!(anchored
+            .solutions
+            .iter()
+            .all(|solution| solution.piece_at(2, 0) == Some(0)));
+
+        // pieces without anchors aren't restricted
+        let unanchored = Solution::solve_with_anchor_score(&start, &[piece("XX", 0)], 3);
+        assert_eq!(
+            unanchored.solutions,
+            Solution::new(&start, &[piece("XX", 0)]).solutions
+        );
+    }
+
+    #[test]
+    fn best_solutions_prefer_more_edge_contact() {
+        let solution = Solution::new(&field("---\n---"), &[piece("X", 0)]);
+        let best = solution.best_solutions();
+        assert_eq!(best.len(), 6);
+        let contacts: Vec<usize> = best.iter().map(Field::edge_contact).collect();
+        assert_eq!(contacts, vec![2, 2, 2, 2, 1, 1]);
+        assert_eq!(best[0], solution.solutions[0]);
+    }
+
+    #[test]
+    fn is_still_solvable_detects_boards_without_room() {
+        let start = field("---\n---");
+        let remaining = [piece("XX", 1), piece("XXX", 2)];
+        let split = start.with_piece_placed(&piece("X", 0), 1, 0).unwrap();
+        assert!(!Solution::is_still_solvable(&split, &remaining));
+        let corner = start.with_piece_placed(&piece("X", 0), 0, 0).unwrap();
+        assert!(Solution::is_still_solvable(&corner, &remaining));
+        assert!(Solution::is_still_solvable(&corner, &[]));
+    }
+
+    #[test]
+    fn unique_solutions_parallel_keeps_the_first_occurrences() {
+        let mut solution =
+            Solution::new(&field("----\n----\n----"), &[piece("XX", 0), piece("X", 1)]);
+        let unique = solution.solutions.clone();
+        solution.solutions.extend(unique.iter().rev().cloned());
+        solution.solutions.extend(unique.iter().take(5).cloned());
+        assert_eq!(solution.unique_solutions_parallel(), unique);
+
+        solution.solutions.clear();
+        assert!(solution.unique_solutions_parallel().is_empty());
+    }
+
+    #[test]
+    fn solve_allow_overhang_places_padded_pieces() {
+        let padded: [Piece; 1] = ["XX \nX  \n   ".parse().unwrap()];
+        let start = field("--\n--");
+        assert_eq!(
+            Solution::solve_allow_overhang(&start, &padded)
+                .solutions
+                .len(),
+            4
+        );
+        assert!(Solution::new(&start, &padded).solutions.is_empty());
+    }
+
+    #[test]
+    fn decorative_pieces_do_not_change_the_scores() {
+        let start = field("19-\n---");
+        let mut decoration = piece("X", 1);
+        decoration.decorative = true;
+        let pieces = [piece("XX", 0), decoration.clone()];
+        let without = Solution::new(&start, &pieces[..1]);
+        let mut with = Solution::new(&start, &pieces);
+        assert_eq!(with.highest_score(), without.highest_score());
+        assert_eq!(with.objective_score(&with.best_solutions()[0]), 10);
+
+        with.objective = Objective::MaxPieces;
+        let mut without = without;
+        without.objective = Objective::MaxPieces;
+        assert_eq!(
+            with.objective_score(&with.best_solutions()[0]),
+            without.objective_score(&without.best_solutions()[0])
+        );
+        assert!(!decoration.canonical().decorative);
+    }
+
+    #[test]
+    fn rankings_use_the_leftover_score_of_decorative_pieces() {
+        let start = field("19-\n---");
+        let mut decoration = piece("X", 1);
+        decoration.decorative = true;
+        let pieces = [piece("XX", 0), decoration];
+        let solution = Solution::new(&start, &pieces);
+
+        let sorted = solution.solutions_by_score();
+        assert_eq!(
+            solution.leftover_score(&sorted[0]),
+            solution.highest_score()
+        );
+        let scores: Vec<i32> = sorted
+            .iter()
+            .map(|field| solution.leftover_score(field))
+            .collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(solution.top_n(1), sorted[..1].to_vec());
+
+        let best_only = Solution::solve_best_only(&start, &pieces);
+        let mut expected = solution.best_solutions();
+        let mut found = best_only.solutions;
+        expected.sort_by_key(Field::to_string);
+        found.sort_by_key(Field::to_string);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn forced_cells_lists_cells_with_a_single_covering_piece() {
+        // 0/0 can only be covered by a vertical domino
+        let start = field("- --\n----");
+        let forced = Solution::forced_cells(&start, &[piece("XX", 3)]);
+        assert!(forced.contains(&(0, 0, 3)));
+        assert!(!forced.iter().any(|&(x, y, _)| (x, y) == (1, 1)));
+        assert!(Solution::forced_cells(&start, &[piece("XX", 3), piece("X", 4)]).is_empty());
+        assert_eq!(
+            Solution::forced_cells(&field("-"), &[piece("X", 2)]),
+            vec![(0, 0, 2)]
+        );
+    }
+
+    #[test]
+    fn solve_with_limits_the_number_of_solutions() {
+        let start = field("12-\n-3-");
+        let pieces = [piece("XX", 0), piece("X", 1)];
+        let all = Solution::new(&start, &pieces);
+        let limited =
+            Solution::solve_with(&start, &pieces, &SolveOptions::default().max_solutions(3));
+        assert_eq!(limited.solutions, all.solutions[..3].to_vec());
+        let none = Solution::solve_with(&start, &pieces, &SolveOptions::default().max_solutions(0));
+        assert!(none.solutions.is_empty());
+    }
+
+    #[test]
+    fn solve_with_combines_the_options() {
+        let start = field("12-\n-3-");
+        let pieces = [piece("XX", 0), piece("X", 1)];
+        let covered = SolveOptions::default()
+            .objective(Objective::Covered)
+            .best_only(true);
+        let best = Solution::solve_with(&start, &pieces, &covered);
+        let mut all = Solution::new(&start, &pieces);
+        all.objective = Objective::Covered;
+        assert_eq!(best.objective, Objective::Covered);
+        assert_eq!(best.solutions, all.best_solutions());
+
+        let perfect = SolveOptions::default()
+            .stop_at_perfect(true)
+            .max_solutions(100);
+        assert_eq!(
+            Solution::solve_with(&start, &pieces, &perfect)
+                .solutions
+                .len(),
+            Solution::solve_until_perfect(&start, &pieces)
+                .solutions
+                .len()
+        );
+
+        let padded: [Piece; 1] = ["XX \n   ".parse().unwrap()];
+        let overhang = SolveOptions::default()
+            .allow_overhang(true)
+            .max_solutions(1);
+        assert_eq!(
+            Solution::solve_with(&field("--"), &padded, &overhang)
+                .solutions
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn score_by_piece_adds_up_to_the_covered_score() {
+        let start = field("12-\n-34");
+        for solution in Solution::new(&start, &[piece("XX", 0), piece("X", 1)])
+            .solutions
+            .iter()
+        {
+            let scores = solution.score_by_piece(&start);
+            assert_eq!(
+                scores.values().sum::<i32>(),
+                start.count() - solution.count()
+            );
+        }
+    }
+
+    #[test]
+    fn solve_cancellable_stops_when_cancelled() {
+        let start = field("--------\n--------\n--------\n--------\n--------\n--------");
+        let pieces: Vec<Piece> = (0..10).map(|id| piece("XX\nX", id)).collect();
+        let cancelled = Arc::new(AtomicBool::new(true));
+        assert!(Solution::solve_cancellable(&start, &pieces, cancelled).is_empty());
+
+        let running = Arc::new(AtomicBool::new(false));
+        let solutions = Solution::solve_cancellable(&field("---"), &[piece("X", 0)], running);
+        assert_eq!(solutions.len(), 3);
+    }
+
+    #[test]
+    fn solve_layered_allows_pieces_to_overlap() {
+        let start = field("---");
+        let dominoes = [piece("XX", 0), piece("XX", 1)];
+        assert!(Solution::new(&start, &dominoes).solutions.is_empty());
+
+        let layered = Solution::solve_layered(&start, &dominoes, 2);
+        assert!(!layered.is_empty());
+        assert!(layered.iter().all(|l| l.depth.iter().all(|&d| d <= 2)));
+        assert!(layered.iter().any(|l| l.depth.contains(&2)));
+
+        let monominoes = [piece("X", 0), piece("X", 1)];
+        assert_eq!(
+            Solution::solve_layered(&start, &monominoes, 1).len(),
+            Solution::new(&start, &monominoes).solutions.len()
+        );
+    }
+
+    #[test]
+    fn solution_placements_replay_to_the_solutions() {
+        let (start, pieces) = puzzle_3();
+        let solution = Solution::new(&start, &pieces);
+        let placements = solution.solution_placements();
+        assert_eq!(placements.len(), solution.solutions.len());
+        for (board, placements) in solution.solutions.iter().zip(&placements) {
+            assert_eq!(placements.len(), 3);
+            let mut replayed = start.clone();
+            for &(id, variant, x, y) in placements {
+                let variants = pieces[usize::from(id)].all_variants();
+                assert!(replayed.try_place(&variants[variant], x, y));
+            }
+            assert_eq!(&replayed, board);
+        }
+    }
+
+    #[test]
+    fn solution_placements_of_padded_pieces() {
+        let corner: Piece = "XX \nX  ".parse().unwrap();
+        let solution = Solution::solve_allow_overhang(&field("--\n--"), &[corner]);
+        let single = solution
+            .solution_placements()
+            .iter()
+            .filter(|placements| placements.len() == 1)
+            .count();
+        assert_eq!(single, 4);
+    }
+
+    #[test]
+    fn sample_is_deterministic_for_a_seed() {
+        let solution = Solution::new(&field("----\n----\n----"), &[piece("XX", 0), piece("X", 1)]);
+        let sample = solution.sample(5, 42);
+        assert_eq!(sample, solution.sample(5, 42));
+        assert_eq!(sample.len(), 5);
+        assert!(solution.sample(5, 43) != sample || solution.sample(5, 44) != sample);
+
+        let distinct: HashSet<&Field> = sample.iter().collect();
+        assert_eq!(distinct.len(), 5);
+        assert!(sample
+            .iter()
+            .all(|board| solution.solutions.contains(board)));
+    }
+
+    #[test]
+    fn sample_returns_at_most_all_solutions() {
+        let solution = Solution::new(&field("----\n----\n----"), &[piece("XX", 0), piece("X", 1)]);
+        assert_eq!(solution.sample(10000, 1).len(), solution.solutions.len());
+        assert!(solution.sample(0, 1).is_empty());
+    }
+
+    #[test]
+    fn sample_reaches_every_solution() {
+        let solution = Solution::new(&field("----\n----\n----"), &[piece("XX", 0), piece("X", 1)]);
+        let mut hits = vec![0; solution.solutions.len()];
+        for seed in 0..2000 {
+            for board in solution.sample(1, seed) {
+                hits[solution.solutions.iter().position(|s| *s == board).unwrap()] += 1;
+            }
+        }
+        assert!(hits.iter().all(|&count| count > 0));
+    }
+
+    #[test]
+    fn for_each_combination_visits_every_subset_of_the_size() {
+        let mut visited = vec![];
+        for_each_combination(4, 2, |indices| {
+            visited.push(indices.to_vec());
+            true
+        });
+        assert_eq!(
+            visited,
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+
+        let mut count = 0;
+        for_each_combination(3, 0, |indices| {
+            assert!(indices.is_empty());
+            count += 1;
+            true
+        });
+        for_each_combination(2, 3, |_| panic!("there are no subsets larger than the set"));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn min_pieces_for_score_finds_the_smallest_subset() {
+        let start = field("59-\n---");
+        let pieces = [piece("X", 0), piece("XX", 1), piece("XXX", 2)];
+        assert_eq!(
+            Solution::min_pieces_for_score(&start, &pieces, 14),
+            Some(vec![1])
+        );
+        assert_eq!(
+            Solution::min_pieces_for_score(&start, &pieces, 9),
+            Some(vec![0])
+        );
+        assert_eq!(
+            Solution::min_pieces_for_score(&start, &pieces, 0),
+            Some(vec![])
+        );
+        assert_eq!(Solution::min_pieces_for_score(&start, &pieces, 15), None);
+    }
+
+    #[test]
+    fn min_pieces_for_score_handles_more_than_64_pieces() {
+        let pieces: Vec<Piece> = (0..70).map(|id| piece("X", id)).collect();
+        assert_eq!(
+            Solution::min_pieces_for_score(&field("9-"), &pieces, 9),
+            Some(vec![0])
+        );
+    }
+
+    #[test]
+    fn score_histogram_counts_the_solutions_per_leftover_score() {
+        let solution = Solution::new(&field("12-\n-2-"), &[piece("XX", 0)]);
+        let histogram = solution.score_histogram();
+        let expected: BTreeMap<i32, usize> = vec![(1, 1), (2, 1), (3, 3), (4, 1), (5, 1)]
+            .into_iter()
+            .collect();
+        assert_eq!(histogram, expected);
+        assert_eq!(histogram.values().sum::<usize>(), solution.solutions.len());
+        assert_eq!(*histogram.keys().last().unwrap(), solution.highest_score());
+    }
+
+    #[test]
+    fn score_histogram_ignores_decorative_pieces() {
+        let mut decorative = piece("X", 0);
+        decorative.decorative = true;
+        let histogram = Solution::new(&field("12"), &[decorative]).score_histogram();
+        assert_eq!(histogram.into_iter().collect::<Vec<_>>(), vec![(3, 2)]);
+    }
+
+    #[test]
+    fn then_solve_continues_from_the_best_solution() {
+        let staged = Solution::new(&field("9--"), &[piece("XX", 0)]).then_solve(&[piece("X", 1)]);
+        assert_eq!(staged.start.to_string(), "9AA");
+        assert_eq!(staged.solutions.len(), 1);
+        assert_eq!(staged.solutions[0].to_string(), "BAA");
+    }
+
+    #[test]
+    fn then_solve_without_solutions_has_no_solutions() {
+        let staged = Solution::new(&field("-"), &[piece("XX", 0)]).then_solve(&[piece("X", 1)]);
+        assert!(staged.solutions.is_empty());
+    }
+
+    #[test]
+    fn successors_are_the_distinct_boards_after_one_placement() {
+        let start = field("---\n---");
+        let pieces = vec![piece("XX", 0).all_variants(), piece("X", 1).all_variants()];
+        let successors = Solution::successors(&start, &pieces);
+        // 3 vertical and 4 horizontal dominoes, 6 monominoes
+        assert_eq!(successors.len(), 7 + 6);
+        assert_eq!(successors.iter().filter(|(_, id)| *id == 1).count(), 6);
+        let distinct: HashSet<&Field> = successors.iter().map(|(board, _)| board).collect();
+        assert_eq!(distinct.len(), successors.len());
+    }
+
+    #[test]
+    fn successors_skip_duplicates_of_padded_variants() {
+        let padded = vec![vec![piece("X ", 0)], vec![piece(" X", 0)]];
+        assert_eq!(Solution::successors(&field("---"), &padded).len(), 3);
+    }
+
+    #[test]
+    fn best_greedy_step_covers_the_most_score() {
+        let start = field("1-9\n--8");
+        let pieces = vec![piece("XX", 0).all_variants(), piece("X", 1).all_variants()];
+        let (board, id, gain) = Solution::best_greedy_step(&start, &pieces).unwrap();
+        assert_eq!((id, gain), (0, 17));
+        assert_eq!(board.to_string(), "1-A\n--A");
+        assert!(Solution::best_greedy_step(&field(" "), &pieces).is_none());
+    }
+
+    #[test]
+    fn best_greedy_step_covers_negative_scores_if_it_has_to() {
+        let mut start = field("--");
+        start.field = vec![FieldState::Free(-3), FieldState::Free(-1)];
+        let (board, _, gain) = Solution::best_greedy_step(&start, &[vec![piece("X", 0)]]).unwrap();
+        assert_eq!(gain, -1);
+        assert_eq!(board.piece_at(1, 0), Some(0));
+    }
+
+    #[test]
+    fn must_touch_border_pieces_stay_on_the_border() {
+        let mut dot = piece("X", 0);
+        let start = field("---\n---\n---");
+        assert_eq!(Solution::new(&start, &[dot.clone()]).solutions.len(), 9);
+
+        dot.must_touch_border = true;
+        let solution = Solution::new(&start, &[dot.clone()]);
+        assert_eq!(solution.solutions.len(), 8);
+        assert!(solution
+            .solutions
+            .iter()
+            .all(|board| board.field[4] == FieldState::Free(0)));
+
+        let big = field("-----\n-----\n-----\n-----\n-----");
+        assert_eq!(Solution::new(&big, &[dot]).solutions.len(), 16);
+    }
+
+    #[test]
+    fn solve_many_matches_solving_each_field() {
+        let fields = vec![field("-1-\n---"), field("--\n-9"), field(" -\n--")];
+        let pieces = [piece("XX", 0), piece("X", 1)];
+        let many = Solution::solve_many(&fields, &pieces);
+        assert_eq!(many.len(), 3);
+        for (start, solution) in fields.iter().zip(&many) {
+            let single = Solution::new(start, &pieces);
+            assert_eq!(solution.start, single.start);
+            assert_eq!(solution.solutions, single.solutions);
+            assert_eq!(solution.stats, single.stats);
+            assert_eq!(solution.pieces.len(), single.pieces.len());
+        }
+        assert!(Solution::solve_many(&[], &pieces).is_empty());
+    }
+
+    #[test]
+    fn check_piece_ids_reports_ids_already_placed_on_the_field() {
+        let start = field("AA-\nC--");
+        let pieces = [piece("X", 0), piece("XX", 1), piece("X", 2)];
+        assert_eq!(Solution::check_piece_ids(&start, &pieces), Err(vec![0, 2]));
+        assert_eq!(Solution::check_piece_ids(&field("---"), &pieces), Ok(()));
+    }
+
+    #[test]
+    fn remap_piece_ids_picks_the_lowest_free_ids() {
+        let start = field("AA-\nC--");
+        let pieces = [piece("X", 0), piece("XX", 1), piece("X", 2)];
+        let remapped = Solution::remap_piece_ids(&start, &pieces).unwrap();
+        let ids: Vec<u8> = remapped.iter().map(|piece| piece.id).collect();
+        assert_eq!(ids, vec![3, 1, 4]);
+        assert!(remapped[2].same_shape(&pieces[2]));
+        assert_eq!(Solution::check_piece_ids(&start, &remapped), Ok(()));
+    }
+
+    #[test]
+    fn remap_piece_ids_fails_without_free_ids() {
+        let mut start = field("-");
+        start.field = (0..=255).map(FieldState::Occupied).collect();
+        start.width = 256;
+        assert!(Solution::remap_piece_ids(&start, &[piece("X", 0)]).is_none());
+    }
+
+    fn decorative(s: &str, id: u8) -> Piece {
+        let mut piece = piece(s, id);
+        piece.decorative = true;
+        piece
+    }
+
+    #[test]
+    fn iter_by_leftover_ascending_uses_the_leftover_score_of_decorative_pieces() {
+        let start = field("9-1");
+        let pieces = [decorative("X", 0), piece("X", 1)];
+        let solution = Solution::new(&start, &pieces);
+        let scores: Vec<i32> = Solution::iter_by_leftover_ascending(&start, &pieces)
+            .map(|board| solution.leftover_score(&board))
+            .collect();
+        assert_eq!(scores.len(), solution.solutions.len());
+        assert_eq!(scores[0], 1);
+        assert!(scores.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn find_meeting_score_uses_the_leftover_score_of_decorative_pieces() {
+        let start = field("9-");
+        let pieces = [decorative("X", 0), piece("X", 1)];
+        let found = Solution::find_meeting_score(&start, &pieces, 9).unwrap();
+        assert_eq!(found.to_string(), "AB");
+        assert!(Solution::find_meeting_score(&start, &pieces, 10).is_none());
+    }
+
+    #[test]
+    fn min_pieces_for_score_ignores_the_cells_of_decorative_pieces() {
+        let start = field("59-\n---");
+        let pieces = [decorative("XX", 0), piece("X", 1)];
+        assert_eq!(
+            Solution::min_pieces_for_score(&start, &pieces, 9),
+            Some(vec![1])
+        );
+        assert_eq!(Solution::min_pieces_for_score(&start, &pieces, 10), None);
+    }
+
+    #[test]
+    fn stop_at_perfect_ignores_the_cells_of_decorative_pieces() {
+        let start = field("9-");
+        let pieces = [decorative("X", 0), piece("X", 1)];
+        let solution = Solution::solve_until_perfect(&start, &pieces);
+        // the decorative piece on the 9 doesn't cover it
+        assert_eq!(solution.solutions.len(), 2);
+        assert_eq!(solution.solutions[1].to_string(), "BA");
+    }
+
+    #[test]
+    fn best_greedy_step_doesnt_count_decorative_pieces() {
+        let pieces = vec![
+            decorative("X", 0).all_variants(),
+            piece("X", 1).all_variants(),
+        ];
+        let (board, id, gain) = Solution::best_greedy_step(&field("9-"), &pieces).unwrap();
+        assert_eq!((board.to_string(), id, gain), ("B-".to_string(), 1, 9));
+
+        let (board, id, gain) = Solution::best_greedy_step(&field("9-"), &pieces[..1]).unwrap();
+        assert_eq!((board.to_string(), id, gain), ("9A".to_string(), 0, 0));
+    }
+
+    #[test]
+    fn greedy_keeps_the_high_scores_free_for_the_other_pieces() {
+        let pieces = [decorative("X", 0), piece("X", 1)];
+        assert_eq!(Solution::greedy(&field("9-"), &pieces).to_string(), "BA");
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::error::ParseError;
+use crate::field::{Field, FieldState, MAX_CELLS};
+use crate::piece::{Piece, MAX_PIECES};
+
+/// The subset of TOML values used by puzzle files.
+#[derive(Debug)]
+enum Value {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Value>),
+}
+
+type Table = HashMap<String, Value>;
+
+fn error<T>(message: &str) -> Result<T, ParseError> {
+    Err(ParseError::InvalidToml(message.to_string()))
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>, newlines: bool) {
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\r' => {}
+            '\n' if newlines => {}
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            _ => return,
+        }
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, ParseError> {
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::String(s)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some(c @ '"') | Some(c @ '\\') => s.push(c),
+                        _ => return error("invalid escape sequence"),
+                    },
+                    Some('\n') | None => return error("unterminated string"),
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+        Some('\'') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') => return Ok(Value::String(s)),
+                    Some('\n') | None => return error("unterminated string"),
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut values = vec![];
+            loop {
+                skip_whitespace(chars, true);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Ok(Value::Array(values));
+                }
+                values.push(parse_value(chars)?);
+                skip_whitespace(chars, true);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(values)),
+                    _ => return error("expected ',' or ']' in array"),
+                }
+            }
+        }
+        Some(_) => {
+            let mut s = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == '-' || c == '+' || c == '_' || c.is_ascii_alphanumeric() {
+                    s.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            match s.as_str() {
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                _ => s
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .or_else(|_| error("expected an integer, boolean, string or array")),
+            }
+        }
+        None => error("missing value"),
+    }
+}
+
+/// Splits the document into its `[field]` table and `[[piece]]` tables.
+fn parse_tables(s: &str) -> Result<(Table, Vec<Table>), ParseError> {
+    let mut field = Table::new();
+    let mut pieces: Vec<Table> = vec![];
+    let mut in_field = false;
+
+    let mut chars = s.chars().peekable();
+    loop {
+        skip_whitespace(&mut chars, true);
+        let key: String = match chars.peek() {
+            None => return Ok((field, pieces)),
+            Some('[') => {
+                let header: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                match header.split('#').next().unwrap_or("").trim_end() {
+                    "[field]" => in_field = true,
+                    "[[piece]]" => {
+                        in_field = false;
+                        pieces.push(Table::new());
+                    }
+                    _ => return error("expected [field] or [[piece]]"),
+                }
+                continue;
+            }
+            Some(_) => {
+                let mut key = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        key.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                key
+            }
+        };
+
+        skip_whitespace(&mut chars, false);
+        if key.is_empty() || chars.next() != Some('=') {
+            return error("expected key = value");
+        }
+        skip_whitespace(&mut chars, false);
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars, false);
+        if chars.peek().is_some_and(|&c| c != '\n') {
+            return error("expected a new line after the value");
+        }
+
+        let table = if in_field {
+            &mut field
+        } else {
+            match pieces.last_mut() {
+                Some(table) => table,
+                None => return error("key outside of a table"),
+            }
+        };
+        if table.insert(key, value).is_some() {
+            return error("duplicate key");
+        }
+    }
+}
+
+fn integer(table: &Table, key: &str) -> Result<Option<i64>, ParseError> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(Value::Integer(i)) => Ok(Some(*i)),
+        Some(_) => error(&format!("{} must be an integer", key)),
+    }
+}
+
+fn boolean(table: &Table, key: &str) -> Result<bool, ParseError> {
+    match table.get(key) {
+        None => Ok(false),
+        Some(Value::Boolean(b)) => Ok(*b),
+        Some(_) => error(&format!("{} must be a boolean", key)),
+    }
+}
+
+fn coordinate(value: i64, max: usize) -> Result<usize, ParseError> {
+    if value >= 0 && (value as u64) < max as u64 {
+        Ok(value as usize)
+    } else {
+        error("cell outside of the field")
+    }
+}
+
+/// Integer arrays of `len` elements, like `[[x, y], ...]`.
+fn tuples(table: &Table, key: &str, len: usize) -> Result<Vec<Vec<i64>>, ParseError> {
+    let entries = match table.get(key) {
+        None => return Ok(vec![]),
+        Some(Value::Array(entries)) => entries,
+        Some(_) => return error(&format!("{} must be an array", key)),
+    };
+
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Value::Array(values) if values.len() == len => values
+                .iter()
+                .map(|value| match value {
+                    Value::Integer(i) => Ok(*i),
+                    _ => error(&format!("{} must only contain integers", key)),
+                })
+                .collect(),
+            _ => error(&format!("{} entries must have {} elements", key, len)),
+        })
+        .collect()
+}
+
+/// Loads a puzzle of the form
+///
+/// ```toml
+/// [field]
+/// width = 3
+/// height = 2
+/// blocked = [[0, 0]]          # optional [x, y] cells
+/// scores = [[2, 1, 5]]        # optional [x, y, score] of free cells, 0 otherwise
+///
+/// [[piece]]
+/// rows = ["XX", " X"]
+/// weight = 1                  # optional
+/// decorative = false          # optional
+/// must_touch_border = false   # optional
+/// ```
+///
+/// The ids of the pieces are assigned in the order of their tables. This isn't a full TOML
+/// parser, only this grammar is understood:
+///
+/// - the tables `[field]` and `[[piece]]`, each key belongs to the table above it
+/// - bare keys, one `key = value` per line
+/// - decimal integers with optional sign and `_` separators, `true` and `false`
+/// - basic strings with the escapes `\n`, `\t`, `\"` and `\\`, and literal strings in `'`
+/// - arrays of these values, which may span several lines and end with a `,`
+/// - `#` comments
+///
+/// Like with a `FIELD` header, the field may have at most 2^20 cells, and there may be at most
+/// 256 pieces.
+pub fn load_puzzle_toml(s: &str) -> Result<(Field, Vec<Piece>), ParseError> {
+    let (table, piece_tables) = parse_tables(s)?;
+
+    let width = integer(&table, "width")?.ok_or(ParseError::InvalidToml("missing width".into()))?;
+    let height =
+        integer(&table, "height")?.ok_or(ParseError::InvalidToml("missing height".into()))?;
+    let cells = width
+        .checked_mul(height)
+        .filter(|_| width >= 0 && height >= 0);
+    let (width, height) = match cells {
+        Some(cells) if cells > 0 && cells as u64 <= MAX_CELLS as u64 => {
+            (width as usize, height as usize)
+        }
+        _ => return error("invalid dimensions"),
+    };
+
+    let mut field = Field {
+        width,
+        height,
+        field: vec![FieldState::Free(0); width * height],
+        zones: BTreeMap::new(),
+    };
+    for cell in tuples(&table, "scores", 3)? {
+        let i = coordinate(cell[0], width)? + coordinate(cell[1], height)? * width;
+        if cell[2] < i64::from(i16::MIN) || cell[2] > i64::from(i16::MAX) {
+            return Err(ParseError::InvalidScore(cell[2].to_string()));
+        }
+        field.field[i] = FieldState::Free(cell[2] as i16);
+    }
+    for cell in tuples(&table, "blocked", 2)? {
+        let i = coordinate(cell[0], width)? + coordinate(cell[1], height)? * width;
+        field.field[i] = FieldState::Blocked;
+    }
+
+    if piece_tables.len() > MAX_PIECES {
+        return Err(ParseError::TooManyPieces);
+    }
+    let pieces = piece_tables
+        .iter()
+        .enumerate()
+        .map(|(id, table)| {
+            let rows = match table.get("rows") {
+                Some(Value::Array(rows)) => rows
+                    .iter()
+                    .map(|row| match row {
+                        Value::String(row) => Ok(row.as_str()),
+                        _ => error("rows must only contain strings"),
+                    })
+                    .collect::<Result<Vec<&str>, ParseError>>()?,
+                _ => return error("a piece requires rows"),
+            };
+
+            let mut piece = Piece::from_rows(&rows)?;
+            piece.id = id as u8;
+            piece.weight = match integer(table, "weight")?.map(i32::try_from) {
+                Some(Ok(weight)) => weight,
+                Some(Err(_)) => return error("weight out of range"),
+                None => 0,
+            };
+            piece.decorative = boolean(table, "decorative")?;
+            piece.must_touch_border = boolean(table, "must_touch_border")?;
+            Ok(piece)
+        })
+        .collect::<Result<Vec<Piece>, ParseError>>()?;
+
+    Ok((field, pieces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUZZLE: &str = r#"
+# puzzle
+[field]
+width = 3
+height = 2
+blocked = [[0, 0]]
+scores = [
+  [2, 1, 5], # comment
+  [1, 0, -2],
+]
+
+[[piece]]
+rows = ["XX", ' X']
+weight = 4
+
+[[piece]]  # second
+rows = ['X']
+decorative = true
+must_touch_border = false
+"#;
+
+    #[test]
+    fn loads_the_field_and_the_pieces() {
+        let (field, pieces) = load_puzzle_toml(PUZZLE).unwrap();
+        let mut expected: Field = " --\n--5".parse().unwrap();
+        expected.field[1] = FieldState::Free(-2);
+        assert_eq!(field, expected);
+
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[0].to_string(), "XX\n X");
+        assert_eq!(
+            (pieces[0].id, pieces[0].weight, pieces[0].decorative),
+            (0, 4, false)
+        );
+        assert_eq!(
+            (pieces[1].id, pieces[1].weight, pieces[1].decorative),
+            (1, 0, true)
+        );
+        assert!(!pieces[1].must_touch_border);
+    }
+
+    #[test]
+    fn rejects_invalid_puzzles() {
+        let invalid = |s: &str| matches!(load_puzzle_toml(s), Err(ParseError::InvalidToml(_)));
+        assert!(invalid("[field]\nwidth=2\n"));
+        assert!(invalid("[field]\nwidth=2\nheight=1\nblocked=[[2,0]]"));
+        assert!(invalid(
+            "[field]\nwidth=2\nheight=1\n[[piece]]\nrows=['X']\ndecorative=1"
+        ));
+        assert!(invalid("[field]\nwidth=2\nheight=1\n[[piece]]\nrows=['X]"));
+        assert!(invalid("[field]\nwidth=20000\nheight=20000\n"));
+        assert!(matches!(
+            load_puzzle_toml("[field]\nwidth=2\nheight=1\n[[piece]]\nrows=[\"Xq\"]"),
+            Err(ParseError::UnexpectedCharacter('q'))
+        ));
+    }
+
+    #[test]
+    fn rejects_more_than_256_pieces() {
+        let puzzle = format!(
+            "[field]\nwidth=1\nheight=1\n{}",
+            "[[piece]]\nrows=[\"X\"]\n".repeat(257)
+        );
+        assert!(matches!(
+            load_puzzle_toml(&puzzle),
+            Err(ParseError::TooManyPieces)
+        ));
+    }
+}
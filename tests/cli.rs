@@ -0,0 +1,275 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+
+/// Runs the binary in the root of the repository, which contains the example puzzles.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_just_in_time_game"))
+        .args(args)
+        .current_dir(Path::new(env!("CARGO_MANIFEST_DIR")))
+        .output()
+        .unwrap()
+}
+
+/// A temporary directory for the test `name`, unique per test process so that concurrent runs
+/// of the tests don't share it.
+fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("just_in_time_game_cli_{}_{}", name, process::id()))
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn count_only_prints_just_the_summary() {
+    let output = run(&["--count-only", "3/F1.txt", "3/P1.txt"]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Number of solutions 328\nHighest score 12\n"
+    );
+}
+
+#[test]
+fn batch_prints_a_csv_row_per_puzzle() {
+    let dir = temp_dir("batch");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("f1"), "12-\n---").unwrap();
+    fs::write(dir.join("p1"), "XX\n\nX").unwrap();
+    fs::write(dir.join("sub/f2"), "--").unwrap();
+    fs::write(dir.join("p2"), "XXX").unwrap();
+    fs::write(dir.join("manifest"), "f1 p1\n\nsub/f2   p2\n").unwrap();
+
+    let output = run(&["--batch", dir.join("manifest").to_str().unwrap()]);
+    assert!(output.status.success());
+    let lines: Vec<String> = stdout(&output).lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        "field,pieces,solution_count,best_score,elapsed_ms"
+    );
+    assert!(lines[1].starts_with("f1,p1,28,3,"));
+    assert!(lines[2].starts_with("sub/f2,p2,0,0,"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn batch_takes_exactly_one_manifest() {
+    for args in [&["--batch"][..], &["--batch", "3/F1.txt", "3/P1.txt"]] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stdout(&output).is_empty());
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+        assert!(stderr.starts_with("usage "));
+    }
+}
+
+#[test]
+fn warns_about_pieces_with_the_same_shape() {
+    let dir = temp_dir("congruent");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), "---\n---").unwrap();
+    fs::write(dir.join("pieces"), "XX\nX\n\nX\nXX").unwrap();
+
+    let output = run(&[
+        "--count-only",
+        dir.join("field").to_str().unwrap(),
+        dir.join("pieces").to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(stderr.contains("warning: pieces A and B have the same shape"));
+    assert!(stdout(&output).starts_with("Number of solutions"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Writes a puzzle to a temporary directory, returns it with the field and pieces paths.
+fn format_puzzle(name: &str, field: &str, pieces: &str) -> (PathBuf, [String; 2]) {
+    let dir = temp_dir(name);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), field).unwrap();
+    fs::write(dir.join("pieces"), pieces).unwrap();
+    let args = [
+        dir.join("field").to_str().unwrap().to_string(),
+        dir.join("pieces").to_str().unwrap().to_string(),
+    ];
+    (dir, args)
+}
+
+#[test]
+fn format_json_prints_a_single_json_document() {
+    let (dir, [field, pieces]) = format_puzzle("json", "1-", "X");
+    let output = run(&["--format", "json", &field, &pieces]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "{\"start\":[\"1-\"],\"solution_count\":2,\"highest_score\":1,\"solutions\":[\
+         {\"board\":[\"A-\"],\"score\":0},{\"board\":[\"1A\"],\"score\":1}]}\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn format_jsonl_prints_a_json_object_per_solution() {
+    let (dir, [field, pieces]) = format_puzzle("jsonl", "1-\n--", "XX");
+    let output = run(&["--format=jsonl", &field, &pieces]);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "{\"board\":[\"A-\",\"A-\"],\"score\":0}\n\
+         {\"board\":[\"1A\",\"-A\"],\"score\":1}\n\
+         {\"board\":[\"AA\",\"--\"],\"score\":0}\n\
+         {\"board\":[\"1-\",\"AA\"],\"score\":1}\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn format_diff_marks_the_placed_pieces_in_lower_case() {
+    let (dir, [field, pieces]) = format_puzzle("diff", "1-", "X");
+
+    let output = run(&["--format=diff", &field, &pieces]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "a-\n\n1a\n\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn format_svg_draws_the_best_solution_or_the_start() {
+    let (dir, [field, pieces]) = format_puzzle("svg", "1-", "X");
+    assert_eq!(
+        stdout(&run(&["--format=svg", &field, &pieces])),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"64\" height=\"32\">\n\
+         <rect x=\"0\" y=\"0\" width=\"32\" height=\"32\" fill=\"#ffffff\" stroke=\"#000000\"/>\n\
+         <text x=\"16\" y=\"16\" text-anchor=\"middle\" dominant-baseline=\"central\">1</text>\n\
+         <rect x=\"32\" y=\"0\" width=\"32\" height=\"32\" fill=\"hsl(0, 70%, 60%)\" stroke=\"#000000\"/>\n\
+         </svg>\n"
+    );
+
+    // without a solution
+    fs::write(&field, "-").unwrap();
+    fs::write(&pieces, "XX").unwrap();
+    assert_eq!(
+        stdout(&run(&["--format=svg", &field, &pieces])),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"32\" height=\"32\">\n\
+         <rect x=\"0\" y=\"0\" width=\"32\" height=\"32\" fill=\"#ffffff\" stroke=\"#000000\"/>\n\
+         </svg>\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unknown_formats_are_rejected() {
+    let (dir, [field, pieces]) = format_puzzle("format", "1-", "X");
+    let output = run(&["--format=xml", &field, &pieces]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr.clone())
+        .unwrap()
+        .contains("unknown format \"xml\""));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn copies_of_a_counted_piece_are_not_warned_about() {
+    let dir = temp_dir("counted");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), "---\n---\n---").unwrap();
+    fs::write(dir.join("pieces"), "x2\nXX\nX\n\nX\nXX").unwrap();
+
+    let output = run(&[
+        "--count-only",
+        dir.join("field").to_str().unwrap(),
+        dir.join("pieces").to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert_eq!(
+        stderr,
+        "warning: pieces A and C have the same shape\nwarning: pieces B and C have the same shape\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn too_many_pieces_are_an_error() {
+    let dir = temp_dir("too_many");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), "---").unwrap();
+    fs::write(dir.join("pieces"), "x300\nX").unwrap();
+
+    let output = run(&[
+        "--count-only",
+        dir.join("field").to_str().unwrap(),
+        dir.join("pieces").to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(stderr.contains("at most 256 pieces are supported"));
+    assert!(!stderr.contains("panicked"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unknown_flags_are_a_usage_error() {
+    let output = run(&["--count-onyl", "3/F1.txt", "3/P1.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).is_empty());
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(stderr.starts_with("unknown flag --count-onyl\nusage "));
+}
+
+#[test]
+fn missing_arguments_are_a_usage_error() {
+    for args in [&[][..], &["3/F1.txt"]] {
+        let output = run(args);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stdout(&output).is_empty());
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+        assert!(stderr.starts_with("usage "));
+    }
+}
+
+#[test]
+fn malformed_puzzles_are_reported_without_panicking() {
+    let dir = temp_dir("malformed");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), "--\n-%").unwrap();
+    fs::write(dir.join("pieces"), "XX").unwrap();
+    let field = dir.join("field").to_str().unwrap().to_string();
+    let pieces = dir.join("pieces").to_str().unwrap().to_string();
+
+    for args in [
+        vec!["--count-only", field.as_str(), pieces.as_str()],
+        vec!["--count-only", "missing", pieces.as_str()],
+        vec!["--count-only", pieces.as_str(), field.as_str()],
+    ] {
+        let output = run(&args);
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+        assert!(stderr.starts_with("error: "), "{}", stderr);
+        assert!(!stderr.contains("panicked"));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn placed_piece_ids_need_remap_ids() {
+    let dir = temp_dir("remap");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("field"), "AA-\n---").unwrap();
+    fs::write(dir.join("pieces"), "X\n\nXX").unwrap();
+    let field = dir.join("field").to_str().unwrap().to_string();
+    let pieces = dir.join("pieces").to_str().unwrap().to_string();
+
+    let output = run(&["--count-only", &field, &pieces]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(stderr.contains("the field already contains the piece ids A, use --remap-ids"));
+
+    let output = run(&["--remap-ids", "--count-only", &field, &pieces]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("Number of solutions"));
+    fs::remove_dir_all(&dir).unwrap();
+}